@@ -0,0 +1,266 @@
+use std::{fmt::Display, sync::Arc};
+
+use rustfft::{Fft, FftPlanner};
+
+use scope_tui::{display::spectrum::{dominant_bin, magnitudes}, music::Note, DataSource};
+
+/// samples at or above this magnitude count as clipped
+const CLIP_THRESHOLD : f64 = 0.9999;
+
+/// size of fft windows averaged together to find dominant frequencies
+const FFT_SIZE : usize = 16384;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Report {
+	pub sample_rate: u32,
+	pub samples: usize,
+	pub duration: f64,
+	pub channels: Vec<ChannelReport>,
+	/// between first two channels, from -1 (opposite phase) to +1 (mono)
+	pub correlation: Option<f64>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ChannelReport {
+	pub name: String,
+	pub peak: f64,
+	pub peak_dbfs: f64,
+	pub rms: f64,
+	pub rms_dbfs: f64,
+	pub crest_factor: f64,
+	pub dc_offset: f64,
+	pub dominant_frequency: Option<f64>,
+	pub note: Option<String>,
+	pub cents: Option<f64>,
+	pub clipped: usize,
+}
+
+/// running statistics of one channel, so that sources don't need to be kept in memory
+struct ChannelStats {
+	count: usize,
+	sum: f64,
+	sum_sq: f64,
+	peak: f64,
+	clipped: usize,
+	window: Vec<f64>,
+	spectrum: Vec<f64>,
+	windows: usize,
+}
+
+impl ChannelStats {
+	fn new() -> Self {
+		ChannelStats {
+			count: 0, sum: 0.0, sum_sq: 0.0, peak: 0.0, clipped: 0, windows: 0,
+			window: Vec::with_capacity(FFT_SIZE),
+			spectrum: vec![0.0; FFT_SIZE / 2],
+		}
+	}
+
+	fn push(&mut self, sample: f64, fft: &Arc<dyn Fft<f64>>) {
+		self.count += 1;
+		self.sum += sample;
+		self.sum_sq += sample * sample;
+		if sample.abs() > self.peak { self.peak = sample.abs() }
+		if sample.abs() >= CLIP_THRESHOLD { self.clipped += 1 }
+		self.window.push(sample);
+		if self.window.len() == FFT_SIZE {
+			for (acc, x) in self.spectrum.iter_mut().zip(magnitudes(&self.window, fft, true)) {
+				*acc += x;
+			}
+			self.windows += 1;
+			self.window.clear();
+		}
+	}
+
+	fn report(mut self, name: String, sample_rate: u32) -> ChannelReport {
+		if self.windows == 0 && self.window.len() > 1 { // too short for a full window, use what we have
+			let fft = FftPlanner::new().plan_fft_forward(self.window.len());
+			self.spectrum = magnitudes(&self.window, &fft, true);
+		}
+		let resolution = sample_rate as f64 / (self.spectrum.len() * 2) as f64;
+		let dominant_frequency = dominant_bin(&self.spectrum).map(|bin| bin * resolution);
+		let nearest = dominant_frequency.and_then(Note::nearest);
+
+		let count = self.count.max(1) as f64;
+		let rms = (self.sum_sq / count).sqrt();
+		ChannelReport {
+			name,
+			peak: self.peak,
+			peak_dbfs: 20.0 * self.peak.log10(),
+			rms,
+			rms_dbfs: 20.0 * rms.log10(),
+			crest_factor: if rms > 0.0 { self.peak / rms } else { 0.0 },
+			dc_offset: self.sum / count,
+			dominant_frequency,
+			note: nearest.as_ref().map(|(note, _)| note.to_string()),
+			cents: nearest.map(|(_, cents)| cents),
+			clipped: self.clipped,
+		}
+	}
+}
+
+/// read from source for given duration (in seconds) or until it runs out, and compute statistics
+pub fn analyze(mut source: Box<dyn DataSource<f64>>, sample_rate: u32, duration: Option<f64>) -> Report {
+	let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+	let limit = duration.map(|d| (d * sample_rate as f64) as usize).unwrap_or(usize::MAX);
+	let mut stats : Vec<ChannelStats> = Vec::new();
+	let mut sum_xy = 0.0;
+	let mut samples = 0;
+
+	while samples < limit {
+		let Some(data) = source.recv() else { break };
+		let len = data.iter().map(|x| x.len()).min().unwrap_or(0).min(limit - samples);
+		if len == 0 { break }
+		while stats.len() < data.len() {
+			stats.push(ChannelStats::new());
+		}
+		for (chan, stat) in data.iter().zip(stats.iter_mut()) {
+			for sample in &chan[..len] {
+				stat.push(*sample, &fft);
+			}
+		}
+		if data.len() >= 2 {
+			sum_xy += data[0][..len].iter().zip(&data[1][..len]).map(|(x, y)| x * y).sum::<f64>();
+		}
+		samples += len;
+	}
+
+	let correlation = match stats.as_slice() {
+		[l, r, ..] if samples > 0 => {
+			let n = samples as f64;
+			let (mean_l, mean_r) = (l.sum / n, r.sum / n);
+			let var_l = l.sum_sq / n - mean_l * mean_l;
+			let var_r = r.sum_sq / n - mean_r * mean_r;
+			let cov = sum_xy / n - mean_l * mean_r;
+			if var_l > 0.0 && var_r > 0.0 { Some((cov / (var_l * var_r).sqrt()).clamp(-1.0, 1.0)) } else { None }
+		},
+		_ => None,
+	};
+
+	Report {
+		sample_rate,
+		samples,
+		duration: samples as f64 / sample_rate as f64,
+		correlation,
+		channels: stats.into_iter()
+			.enumerate()
+			.map(|(i, stat)| stat.report(channel_name(i), sample_rate))
+			.collect(),
+	}
+}
+
+fn channel_name(index: usize) -> String {
+	match index {
+		0 => "L".into(),
+		1 => "R".into(),
+		_ => format!("{}", index),
+	}
+}
+
+impl Display for Report {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		writeln!(f, "analyzed {:.3}s ({} samples at {}Hz)", self.duration, self.samples, self.sample_rate)?;
+		for chan in &self.channels {
+			writeln!(f)?;
+			writeln!(f, "[{}]", chan.name)?;
+			writeln!(f, "  peak       {:.4} ({:.2} dBFS)", chan.peak, chan.peak_dbfs)?;
+			writeln!(f, "  rms        {:.4} ({:.2} dBFS)", chan.rms, chan.rms_dbfs)?;
+			writeln!(f, "  crest      {:.3} ({:.2} dB)", chan.crest_factor, 20.0 * chan.crest_factor.log10())?;
+			writeln!(f, "  dc offset  {:+.5}", chan.dc_offset)?;
+			match (chan.dominant_frequency, &chan.note, chan.cents) {
+				(Some(freq), Some(note), Some(cents)) => writeln!(f, "  dominant   {:.2}Hz ({} {:+.0} cents)", freq, note, cents)?,
+				(Some(freq), _, _) => writeln!(f, "  dominant   {:.2}Hz", freq)?,
+				_ => writeln!(f, "  dominant   -")?,
+			}
+			writeln!(f, "  clipped    {} samples", chan.clipped)?;
+		}
+		if let Some(correlation) = self.correlation {
+			writeln!(f)?;
+			writeln!(f, "stereo correlation {:+.3}", correlation)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::f64::consts::PI;
+
+	use scope_tui::Matrix;
+
+	use super::*;
+
+	const RATE : u32 = 48000;
+
+	/// hands out given channels in chunks, like a capture would
+	struct Samples(Vec<Vec<f64>>);
+
+	impl DataSource<f64> for Samples {
+		fn recv(&mut self) -> Option<Matrix<f64>> {
+			let len = self.0.first().map(|x| x.len().min(4096)).unwrap_or(0);
+			if len == 0 { return None }
+			Some(self.0.iter_mut().map(|x| x.drain(..len).collect()).collect())
+		}
+	}
+
+	fn sine(freq: f64, amplitude: f64, seconds: f64) -> Vec<f64> {
+		(0..(seconds * RATE as f64) as usize)
+			.map(|i| amplitude * (2.0 * PI * freq * i as f64 / RATE as f64).sin())
+			.collect()
+	}
+
+	fn run(channels: Vec<Vec<f64>>, duration: Option<f64>) -> Report {
+		analyze(Box::new(Samples(channels)), RATE, duration)
+	}
+
+	#[test]
+	fn sine_at_440_is_a4() {
+		let report = run(vec![sine(440.0, 0.5, 1.0)], None);
+		let chan = &report.channels[0];
+		assert!((chan.dominant_frequency.unwrap() - 440.0).abs() < 1.0, "{:?}", chan.dominant_frequency);
+		assert_eq!(chan.note.as_deref(), Some("A4"));
+		assert!(chan.cents.unwrap().abs() < 5.0);
+		assert!((chan.peak - 0.5).abs() < 1e-3);
+		assert!((chan.rms - 0.5 / 2f64.sqrt()).abs() < 1e-3);
+		assert!((chan.crest_factor - 2f64.sqrt()).abs() < 1e-2);
+		assert!(chan.dc_offset.abs() < 1e-3);
+		assert_eq!(chan.clipped, 0);
+		assert_eq!(report.correlation, None);
+	}
+
+	#[test]
+	fn full_scale_square_is_clipped() {
+		let square : Vec<f64> = (0..RATE as usize).map(|i| if (i / 100) % 2 == 0 { 1.0 } else { -1.0 }).collect();
+		let report = run(vec![square], None);
+		let chan = &report.channels[0];
+		assert_eq!(chan.clipped, RATE as usize);
+		assert!((chan.crest_factor - 1.0).abs() < 1e-9);
+		assert!(chan.peak_dbfs.abs() < 1e-9);
+	}
+
+	#[test]
+	fn correlation_of_stereo_pairs() {
+		let tone = sine(1000.0, 0.8, 0.5);
+		let inverted = tone.iter().map(|x| -x).collect();
+		let other = sine(1234.0, 0.8, 0.5);
+		let correlation = |l: &Vec<f64>, r: Vec<f64>| run(vec![l.clone(), r], None).correlation.unwrap();
+		assert!((correlation(&tone, tone.clone()) - 1.0).abs() < 1e-9);
+		assert!((correlation(&tone, inverted) + 1.0).abs() < 1e-9);
+		assert!(correlation(&tone, other).abs() < 0.05);
+		assert_eq!(run(vec![tone.clone(), vec![0.0; tone.len()]], None).correlation, None);
+	}
+
+	#[test]
+	fn stops_after_duration() {
+		let report = run(vec![sine(440.0, 0.5, 1.0); 2], Some(0.25));
+		assert_eq!(report.samples, RATE as usize / 4);
+		assert_eq!(report.channels.len(), 2);
+		assert!((report.duration - 0.25).abs() < 1e-9);
+	}
+
+	#[test]
+	fn short_source_still_finds_frequency() {
+		let report = run(vec![sine(1000.0, 0.5, 0.1)], None);
+		assert!((report.channels[0].dominant_frequency.unwrap() - 1000.0).abs() < 10.0);
+	}
+}
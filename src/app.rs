@@ -1,21 +1,57 @@
 
-use std::{io, ops::Range, time::{Duration, Instant}};
+use std::{io, ops::Range, path::{Path, PathBuf}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
 use ratatui::{
-	style::Color, widgets::{Table, Row, Cell}, symbols::Marker,
+	style::Color, widgets::{Table, Row, Cell, Block, Borders, Clear}, symbols::Marker,
 	backend::Backend,
-	widgets::Chart,
-	Terminal, style::{Style, Modifier}, layout::{Rect, Constraint}
+	Terminal, style::{Style, Modifier}, layout::{Rect, Constraint, Direction, Layout}
 };
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::event::{self, Event, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 
-use crate::{display::{oscilloscope::Oscilloscope, spectroscope::Spectroscope, vectorscope::Vectorscope, Dimension, DisplayMode, GraphConfig}, input::{Matrix, DataSource}};
+use crate::{keymap::{Action, Control, Keymap}, display::{oscilloscope::Oscilloscope, Persistence, persistence::Colormap, Readout, Measurement, measure::Averager, spectroscope::Spectroscope, vectorscope::Vectorscope, widget, Cursors, DataSet, Dimension, DisplayMode, GraphConfig, Theme}, input::{Matrix, DataSource, StreamOptions}, snapshot::{self, Size, SnapshotError}};
 
+/// how long status messages stay in the header
+const STATUS_TIMEOUT : Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
 pub enum CurrentDisplayMode {
+	#[default]
 	Oscilloscope,
 	Vectorscope,
 	Spectroscope,
 }
 
+/// why [App::run] returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+	/// user asked to quit
+	Quit,
+	/// event source asked to restart capture, like when source options changed in configuration
+	Restart,
+}
+
+/// where the app gets user input from while running
+pub trait EventSource {
+	/// next enqueued event, without blocking
+	fn poll(&mut self) -> Result<Option<Event>, io::Error>;
+
+	/// change app settings between frames, like when a configuration file changes.
+	/// returns true if capture should be restarted
+	fn reload(&mut self, _app: &mut App) -> bool { false }
+}
+
+/// read events from the attached terminal, via crossterm
+pub struct TerminalEvents;
+
+impl EventSource for TerminalEvents {
+	fn poll(&mut self) -> Result<Option<Event>, io::Error> {
+		if event::poll(Duration::from_millis(0))? {
+			Ok(Some(event::read()?))
+		} else {
+			Ok(None)
+		}
+	}
+}
+
 pub struct App {
 	#[allow(unused)] channels: u8,
 	graph: GraphConfig,
@@ -23,94 +59,616 @@ pub struct App {
 	vectorscope: Vectorscope,
 	spectroscope: Spectroscope,
 	mode: CurrentDisplayMode,
+	data: Matrix<f64>,
+	datasets: Vec<DataSet>,
+	/// which of last datasets are traces of processed data, after references
+	traces: Range<usize>,
+	fps: usize,
+	framerate: usize,
+	/// when framerate was last counted, in seconds of [App::clock]
+	last_poll: f64,
+	started: Instant,
+	/// seconds of audio received, counted instead of wall clock time when rendering offline
+	played: Option<f64>,
+	snapshot_size: Size,
+	snapshot_dir: PathBuf,
+	status: Option<(String, Instant)>,
+	themes: Vec<(String, Theme)>,
+	theme: usize,
+	keymap: Keymap,
+	help: bool,
+	plot: Rect,
+	drag: Option<Drag>,
+	cursors: Cursors,
+	measurements: bool,
+	averager: Averager,
+	persistence: Persistence,
 }
 
-// TODO another way to build this that doesn't require getting cli args directly!!!
-impl App {
-	pub fn new(ui: &crate::cfg::UiOptions, source: &crate::cfg::SourceOptions) -> Self {
-		let graph = GraphConfig {
-			axis_color: Color::DarkGray,
-			labels_color: Color::Cyan,
-			palette: vec![Color::Red, Color::Yellow, Color::Green, Color::Magenta],
-			scale: ui.scale as f64,
-			width: source.buffer, // TODO also make bit depth customizable
-			samples: source.buffer,
-			sampling_rate: source.sample_rate,
-			references: !ui.no_reference,
-			show_ui: !ui.no_ui,
-			scatter: ui.scatter,
-			pause: false,
-			marker_type: if ui.no_braille {
-				Marker::Dot
-			} else {
-				Marker::Braille
-			},
-		};
+/// which settings [App::reconfigure] takes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Changes {
+	/// graph, snapshot, measurements and persistence settings
+	pub ui: bool,
+	/// display mode to switch to
+	pub mode: bool,
+	/// theme to switch to, instead of keeping current one
+	pub theme: bool,
+	/// themes to choose from
+	pub themes: bool,
+	pub keymap: bool,
+	pub oscilloscope: bool,
+	pub vectorscope: bool,
+	/// averaging, windowing, log scale and grid, keeping what was captured
+	pub spectroscope: bool,
+}
+
+/// mouse button held down, from where, and where it was last seen
+#[derive(Debug, Clone, Copy)]
+struct Drag {
+	button: MouseButton,
+	from: (u16, u16),
+	last: (u16, u16),
+}
+
+/// build an App programmatically, without going through cli options
+#[derive(Default)]
+pub struct AppBuilder {
+	graph: GraphConfig,
+	mode: CurrentDisplayMode,
+	channels: usize,
+	oscilloscope: Option<Oscilloscope>,
+	vectorscope: Option<Vectorscope>,
+	spectroscope: Option<Spectroscope>,
+	snapshot_size: Option<Size>,
+	snapshot_dir: Option<PathBuf>,
+	themes: Option<Vec<(String, Theme)>>,
+	theme: Option<String>,
+	keymap: Keymap,
+	measurements: bool,
+	averager: Averager,
+	persistence: Persistence,
+}
+
+impl AppBuilder {
+	/// sampling rate, buffer size, colors and initial view settings
+	pub fn graph(mut self, graph: GraphConfig) -> Self {
+		self.graph = graph;
+		self
+	}
+
+	/// trace colors, cycled through by channel. an empty palette is ignored
+	pub fn palette(mut self, palette: Vec<Color>) -> Self {
+		if !palette.is_empty() { // would break channel colors
+			self.graph.palette = palette;
+		}
+		self
+	}
+
+	pub fn axis_color(mut self, color: Color) -> Self {
+		self.graph.axis_color = color;
+		self
+	}
+
+	pub fn labels_color(mut self, color: Color) -> Self {
+		self.graph.labels_color = color;
+		self
+	}
+
+	/// sampling rate, buffer size and channels of source, set after [AppBuilder::graph]
+	pub fn stream(mut self, opts: &StreamOptions) -> Self {
+		self.graph.width = opts.buffer; // TODO also make bit depth customizable
+		self.graph.samples = opts.buffer;
+		self.graph.sampling_rate = opts.sample_rate;
+		self.channels = opts.channels;
+		self
+	}
+
+	/// themes to choose from and cycle through, built-in ones if not set
+	pub fn themes(mut self, themes: Vec<(String, Theme)>) -> Self {
+		self.themes = Some(themes);
+		self
+	}
+
+	/// theme with given name, applied to graph colors when built over palette and colors set before.
+	/// ignored if there's no such theme among [AppBuilder::themes]
+	pub fn theme(mut self, name: &str) -> Self {
+		self.theme = Some(name.into());
+		self
+	}
+
+	/// display mode to start in
+	pub fn mode(mut self, mode: CurrentDisplayMode) -> Self {
+		self.mode = mode;
+		self
+	}
+
+	pub fn channels(mut self, channels: usize) -> Self {
+		self.channels = channels;
+		self
+	}
+
+	pub fn oscilloscope(mut self, oscilloscope: Oscilloscope) -> Self {
+		self.oscilloscope = Some(oscilloscope);
+		self
+	}
+
+	pub fn vectorscope(mut self, vectorscope: Vectorscope) -> Self {
+		self.vectorscope = Some(vectorscope);
+		self
+	}
+
+	/// sampling rate and buffer size will be taken from graph config
+	pub fn spectroscope(mut self, spectroscope: Spectroscope) -> Self {
+		self.spectroscope = Some(spectroscope);
+		self
+	}
+
+	/// resolution and directory of snapshots taken while running
+	pub fn snapshots(mut self, size: Size, dir: PathBuf) -> Self {
+		self.snapshot_size = Some(size);
+		self.snapshot_dir = Some(dir);
+		self
+	}
+
+	/// show measurements panel, averaging them over given frames
+	pub fn measurements(mut self, show: bool, frames: u32) -> Self {
+		self.measurements = show;
+		self.averager.frames = frames.max(1);
+		self
+	}
 
-		let oscilloscope = Oscilloscope::from_args(source);
-		let vectorscope = Vectorscope::from_args(source);
-		let spectroscope = Spectroscope::from_args(source);
+	/// draw traces with persistence, fading over given seconds, or never if infinite
+	pub fn persistence(mut self, enabled: bool, decay: f64) -> Self {
+		self.persistence.enabled = enabled;
+		self.persistence.infinite = decay.is_infinite();
+		if decay.is_finite() { self.persistence.decay = decay.max(0.01) }
+		self
+	}
 
-		App { 
-			graph, oscilloscope, vectorscope, spectroscope,
-			mode: CurrentDisplayMode::Oscilloscope,
-			channels: source.channels as u8,
+	/// draw traces as heatmap of hits, colored with given colormap
+	pub fn heatmap(mut self, enabled: bool, colormap: Colormap) -> Self {
+		self.persistence.heatmap = enabled;
+		self.persistence.colormap = colormap;
+		self
+	}
+
+	/// keys bound to each action, and how much modifiers scale them
+	pub fn keymap(mut self, keymap: Keymap) -> Self {
+		self.keymap = keymap;
+		self
+	}
+
+	pub fn build(mut self) -> App {
+		let mut spectroscope = self.spectroscope.unwrap_or_default();
+		spectroscope.sampling_rate = self.graph.sampling_rate;
+		spectroscope.buffer_size = self.graph.width;
+
+		let themes = self.themes.filter(|x| !x.is_empty()).unwrap_or_else(Theme::builtin);
+		let theme = themes.iter().position(|(x, _)| Some(x) == self.theme.as_ref());
+		if let Some(index) = theme {
+			themes[index].1.apply(&mut self.graph);
+		}
+
+		App {
+			graph: self.graph,
+			oscilloscope: self.oscilloscope.unwrap_or_default(),
+			vectorscope: self.vectorscope.unwrap_or_default(),
+			spectroscope,
+			mode: self.mode,
+			channels: self.channels as u8,
+			data: Matrix::default(),
+			datasets: Vec::new(),
+			traces: 0..0,
+			fps: 0,
+			framerate: 0,
+			last_poll: 0.0,
+			started: Instant::now(),
+			played: None,
+			snapshot_size: self.snapshot_size.unwrap_or(Size { width: 1920, height: 1080 }),
+			snapshot_dir: self.snapshot_dir.unwrap_or(PathBuf::from(".")),
+			status: None,
+			themes,
+			theme: theme.unwrap_or(0),
+			keymap: self.keymap,
+			help: false,
+			plot: Rect::default(),
+			drag: None,
+			cursors: Cursors::default(),
+			measurements: self.measurements,
+			averager: self.averager,
+			persistence: self.persistence,
 		}
 	}
+}
 
-	pub fn run<T : Backend>(&mut self, mut source: Box<dyn DataSource<f64>>, terminal: &mut Terminal<T>) -> Result<(), io::Error> {
-		let mut fps = 0;
-		let mut framerate = 0;
-		let mut last_poll = Instant::now();
-		let mut channels = Matrix::default();
-	
+impl App {
+	pub fn builder() -> AppBuilder {
+		AppBuilder::default()
+	}
+
+	/// build an App for a source with given options, with default settings
+	pub fn new(opts: &StreamOptions) -> Self {
+		App::builder()
+			.stream(opts)
+			.oscilloscope(Oscilloscope::from_options(opts))
+			.vectorscope(Vectorscope::from_options(opts))
+			.spectroscope(Spectroscope::from_options(opts))
+			.build()
+	}
+
+	/// draw frames from source until user quits. between frames, events can change settings,
+	/// or make this return so that capture can be restarted
+	pub fn run<T : Backend>(&mut self, mut source: Box<dyn DataSource<f64>>, terminal: &mut Terminal<T>, events: &mut dyn EventSource) -> Result<Exit, io::Error> {
 		loop {
 			let data = source.recv()
 				.ok_or(io::Error::new(io::ErrorKind::BrokenPipe, "data source returned null"))?;
-	
-			if !self.graph.pause {
-				channels = data;
+
+			self.update(data);
+			self.draw(terminal)?;
+
+			while let Some(event) = events.poll()? { // process all enqueued events
+				if self.handle(event)? { return Ok(Exit::Quit); }
 			}
-	
-			fps += 1;
-	
-			if last_poll.elapsed().as_secs() >= 1 {
-				framerate = fps;
-				fps = 0;
-				last_poll = Instant::now();
+
+			if events.reload(self) { return Ok(Exit::Restart); }
+		}
+	}
+
+	/// adapt to a (re)started source with given options
+	pub fn set_stream(&mut self, source: &StreamOptions) {
+		self.graph.width = source.buffer;
+		self.graph.samples = source.buffer;
+		self.graph.sampling_rate = source.sample_rate;
+		self.channels = source.channels as u8;
+		self.spectroscope.sampling_rate = source.sample_rate;
+		self.spectroscope.buffer_size = source.buffer;
+		self.spectroscope.buf.clear();
+	}
+
+	/// show a message in the header for a few seconds, only first line fits
+	pub fn set_status(&mut self, msg: String) {
+		let msg = msg.lines().next().unwrap_or_default().to_string();
+		self.status = Some((msg, Instant::now()));
+	}
+
+	/// count time by audio received instead of wall clock, so that framerate and fading
+	/// don't depend on how fast frames are processed and same input always draws the same
+	pub fn set_offline(&mut self) {
+		self.played = Some(0.0);
+		self.last_poll = 0.0;
+	}
+
+	/// seconds since start, of audio or wall clock time
+	fn clock(&self) -> f64 {
+		self.played.unwrap_or_else(|| self.started.elapsed().as_secs_f64())
+	}
+
+	/// feed a new frame of data, ignored while paused
+	pub fn update(&mut self, data: Matrix<f64>) {
+		if let Some(played) = &mut self.played {
+			*played += data.first().map(|x| x.len()).unwrap_or(0) as f64 / self.graph.sampling_rate.max(1) as f64;
+		}
+		if !self.graph.pause {
+			self.data = data;
+		}
+
+		self.fps += 1;
+
+		if self.clock() - self.last_poll >= 1.0 {
+			self.framerate = self.fps;
+			self.fps = 0;
+			self.last_poll = self.clock();
+		}
+	}
+
+	/// process current data with current display mode and draw it on given terminal
+	pub fn draw<T : Backend>(&mut self, terminal: &mut Terminal<T>) -> Result<(), io::Error> {
+		// traces only need as many points as can be drawn, but drawn by intensity all hits count
+		self.graph.columns = match self.persistence.active() && self.current_display().persistent() {
+			true => 0,
+			false => self.plot.width as u32 * if self.graph.marker_type == Marker::Braille { 2 } else { 1 },
+		};
+		let (mut datasets, mut traces) = self.layers();
+		self.traces = datasets.len()..datasets.len() + traces.len();
+		datasets.append(&mut traces);
+		datasets.append(&mut self.cursors.datasets(self.current_display(), &self.graph));
+		self.datasets = datasets;
+		let measured = match self.measurements && self.graph.show_ui {
+			true => self.averager.update(self.current_display().measure(&self.graph, &self.data)).to_vec(),
+			false => Vec::new(),
+		};
+		terminal.draw(|f| {
+			let mut size = f.size();
+			if self.graph.show_ui {
+				let area = Rect { x: size.x, y: size.y, width: size.width, height:1 }; // a 1px line at the top
+				match &self.status {
+					Some((msg, at)) if at.elapsed() < STATUS_TIMEOUT => f.render_widget(
+						make_status(&self.graph, msg, self.current_display().mode_str()), area
+					),
+					_ => f.render_widget(
+						make_header(&self.graph, &self.current_display().header(&self.graph), self.current_display().mode_str(), self.framerate, self.graph.pause, divisions(self.current_display(), &self.graph)),
+						area
+					),
+				}
+				size.height = size.height.saturating_sub(1);
+				size.y += 1;
+			}
+			let mut panels = Vec::new(); // go beside the plot, never over it
+			if !measured.is_empty() {
+				panels.push(make_measurements(&self.graph, &measured, self.current_display()));
+			}
+			if self.graph.show_ui && !self.cursors.is_empty() {
+				let readout = self.current_display().readout(&self.graph, &self.cursors, &self.datasets);
+				panels.push(make_readout(&self.graph, readout, self.cursors.selected));
 			}
-	
-			{
-				let mut datasets = Vec::new();
-				let graph = self.graph.clone(); // TODO cheap fix...
-				if self.graph.references {
-					datasets.append(&mut self.current_display_mut().references(&graph));
+			if !panels.is_empty() {
+				let width = panels.iter().map(|(_, w, _)| *w).max().unwrap_or(0);
+				let chunks = Layout::default()
+					.direction(Direction::Horizontal)
+					.constraints([Constraint::Min(0), Constraint::Length(width)])
+					.split(size);
+				let mut heights : Vec<Constraint> = panels.iter().map(|(_, _, h)| Constraint::Length(*h)).collect();
+				heights.push(Constraint::Min(0));
+				let rows = Layout::default().direction(Direction::Vertical).constraints(heights).split(chunks[1]);
+				for ((panel, _, _), area) in panels.into_iter().zip(rows.iter()) {
+					f.render_widget(panel, *area);
 				}
-				datasets.append(&mut self.current_display_mut().process(&graph, &channels));
-				terminal.draw(|f| {
-					let mut size = f.size();
-					if self.graph.show_ui {
-						f.render_widget(
-							make_header(&self.graph, &self.current_display().header(&self.graph), self.current_display().mode_str(), framerate, self.graph.pause),
-							Rect { x: size.x, y: size.y, width: size.width, height:1 } // a 1px line at the top
-						);
-						size.height -= 1;
-						size.y += 1;
-					}
-					let chart = Chart::new(datasets.iter().map(|x| x.into()).collect())
-						.x_axis(self.current_display().axis(&self.graph, Dimension::X)) // TODO allow to have axis sometimes?
-						.y_axis(self.current_display().axis(&self.graph, Dimension::Y));
-					f.render_widget(chart, size)
-				})?;
+				size = chunks[0];
 			}
+			let persistent = self.persistence.active() && self.current_display().persistent();
+			if persistent { // traces are drawn on their own, over the rest
+				let others = self.datasets.iter().enumerate().filter(|(i, _)| !self.traces.contains(i)).map(|(_, x)| x);
+				f.render_widget(widget::chart(self.current_display(), &self.graph, others), size);
+			} else {
+				f.render_widget(widget::chart(self.current_display(), &self.graph, &self.datasets), size);
+			}
+			self.plot = widget::plot_area(self.current_display(), &self.graph, size);
+			if persistent {
+				let display = match self.mode { // borrowed apart from persistence
+					CurrentDisplayMode::Oscilloscope => &self.oscilloscope as &dyn DisplayMode,
+					CurrentDisplayMode::Vectorscope => &self.vectorscope as &dyn DisplayMode,
+					CurrentDisplayMode::Spectroscope => &self.spectroscope as &dyn DisplayMode,
+				};
+				let now = self.clock();
+				self.persistence.update(display, &self.graph, self.plot, &self.datasets[self.traces.clone()], now);
+				f.render_widget(&self.persistence, self.plot);
+			}
+			widget::annotate(self.current_display(), &self.graph, self.plot, f.buffer_mut());
+			if let Some(Drag { button: MouseButton::Right, from, last }) = self.drag { // zoom selection
+				let area = Rect {
+					x: from.0.min(last.0), y: from.1.min(last.1),
+					width: from.0.abs_diff(last.0) + 1, height: from.1.abs_diff(last.1) + 1,
+				}.intersection(self.plot);
+				f.render_widget(Block::default().borders(Borders::ALL).border_style(Style::default().fg(self.graph.labels_color)), area);
+			}
+			if self.help {
+				let (help, width, height) = make_help(&self.graph, &self.keymap, self.controls(), self.current_display());
+				let area = centered(size, width, height);
+				f.render_widget(Clear, area);
+				f.render_widget(help, area);
+			}
+		})?;
+		Ok(())
+	}
 
-			while event::poll(Duration::from_millis(0))? { // process all enqueued events
-				let event = event::read()?;
-
-				if self.process_events(event.clone())? { return Ok(()); }
-				self.current_display_mut().handle(event);
+	/// process an user event, returns true if app should quit
+	pub fn handle(&mut self, event: Event) -> Result<bool, io::Error> {
+		if let Event::Mouse(mouse) = event {
+			self.mouse(mouse);
+		}
+		if let Event::Key(key) = event {
+			for (action, magnitude) in self.keymap.actions(key) {
+				if self.help && action == Action::Reset { // just close help
+					self.help = false;
+					continue;
+				}
+				if self.current_display().captures(action) {
+					self.current_display_mut().perform(action, magnitude);
+					continue;
+				}
+				if self.perform(action, magnitude) { return Ok(true); }
+				self.current_display_mut().perform(action, magnitude);
 			}
 		}
+		Ok(false)
+	}
+
+	/// process current data and render it to an image file, without drawing on terminal
+	pub fn export(&mut self, path: &Path) -> Result<(), SnapshotError> {
+		let datasets = self.datasets();
+		self.snapshot(&datasets, path)
+	}
+
+	fn datasets(&mut self) -> Vec<DataSet> {
+		let (mut datasets, mut traces) = self.layers();
+		datasets.append(&mut traces);
+		datasets
+	}
+
+	/// references and traces of current data, kept apart
+	fn layers(&mut self) -> (Vec<DataSet>, Vec<DataSet>) {
+		let graph = self.graph.clone(); // TODO cheap fix...
+		let data = std::mem::take(&mut self.data);
+		let layers = widget::layers(self.current_display_mut(), &graph, &data);
+		self.data = data;
+		layers
+	}
+
+	fn snapshot(&self, datasets: &[DataSet], path: &Path) -> Result<(), SnapshotError> {
+		let svg = snapshot::svg(&self.graph, self.current_display(), datasets, self.snapshot_size);
+		snapshot::save(path, &svg)
+	}
+
+	/// save last drawn frame in snapshot directory, both as svg and png
+	fn take_snapshot(&mut self) {
+		let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_millis()).unwrap_or(0);
+		let path = self.snapshot_dir.join(format!("scope-tui-{}-{}.svg", self.current_display().mode_str(), timestamp));
+		let mut res = self.snapshot(&self.datasets, &path);
+		if cfg!(feature = "png") && res.is_ok() {
+			res = self.snapshot(&self.datasets, &path.with_extension("png"));
+		}
+		let msg = match res {
+			Ok(()) => format!("saved {}", path.with_extension("").display()),
+			Err(e) => format!("snapshot failed: {}", e),
+		};
+		self.set_status(msg);
+	}
+
+	/// scroll zooms Y, or X with shift or ctrl held, left drag pans, left click is passed to
+	/// display mode, middle button moves selected cursor and right drag zooms on selected area
+	fn mouse(&mut self, event: MouseEvent) {
+		let pos = (event.column, event.row);
+		let x_axis = event.modifiers.intersects(KeyModifiers::SHIFT | KeyModifiers::CONTROL);
+		let zoom = match event.kind {
+			MouseEventKind::ScrollUp if x_axis => Some(Action::XRangeDown),
+			MouseEventKind::ScrollDown if x_axis => Some(Action::XRangeUp),
+			MouseEventKind::ScrollUp => Some(Action::YRangeDown),
+			MouseEventKind::ScrollDown => Some(Action::YRangeUp),
+			MouseEventKind::ScrollLeft => Some(Action::XRangeDown),
+			MouseEventKind::ScrollRight => Some(Action::XRangeUp),
+			_ => None,
+		};
+		if let Some(action) = zoom {
+			self.perform(action, 1.0);
+			return;
+		}
+
+		match (event.kind, self.drag) {
+			(MouseEventKind::Down(button), _) if self.plot.intersects(Rect::new(pos.0, pos.1, 1, 1)) => {
+				self.drag = Some(Drag { button, from: pos, last: pos });
+				if button == MouseButton::Middle { self.place_cursor(pos) }
+			},
+			(MouseEventKind::Drag(button), Some(mut drag)) if drag.button == button => {
+				if button == MouseButton::Middle { self.place_cursor(pos) }
+				if button == MouseButton::Left {
+					let display = self.current_display();
+					let (x0, y0) = display.coords(&self.graph, self.plot, drag.last.0, drag.last.1);
+					let (x1, y1) = display.coords(&self.graph, self.plot, pos.0, pos.1);
+					self.graph.offset[0] -= x1 - x0;
+					self.graph.offset[1] -= y1 - y0;
+				}
+				drag.last = pos;
+				self.drag = Some(drag);
+			},
+			(MouseEventKind::Up(button), Some(drag)) if drag.button == button => {
+				self.drag = None;
+				match button {
+					MouseButton::Left if drag.from == pos => {
+						let (x, y) = self.current_display().coords(&self.graph, self.plot, pos.0, pos.1);
+						let graph = self.graph.clone();
+						self.current_display_mut().click(&graph, x, y);
+					},
+					MouseButton::Right if drag.from.0 != pos.0 && drag.from.1 != pos.1 => {
+						let display = self.current_display();
+						let (x0, y0) = display.coords(&self.graph, self.plot, drag.from.0, drag.from.1);
+						let (x1, y1) = display.coords(&self.graph, self.plot, pos.0, pos.1);
+						let mut graph = self.graph.clone();
+						display.zoom(&mut graph, Dimension::X, [x0.min(x1), x0.max(x1)]);
+						display.zoom(&mut graph, Dimension::Y, [y0.min(y1), y0.max(y1)]);
+						self.graph = graph;
+					},
+					_ => {},
+				}
+			},
+			_ => {},
+		}
+	}
+
+	/// move selected cursor to terminal cell, starting with first one if none is shown
+	fn place_cursor(&mut self, (column, row): (u16, u16)) {
+		let (x, y) = self.current_display().coords(&self.graph, self.plot, column, row);
+		self.cursors.place(x, y);
+	}
+
+	/// global controls and their current values, shown in help
+	fn controls(&self) -> Vec<Control> {
+		vec![
+			Control::action(Action::Quit, "quit"),
+			Control::action(Action::Help, "toggle this help"),
+			Control::new(&[Action::Pause], "pause", if self.graph.pause { "paused" } else { "running" }),
+			Control::new(&[Action::NextMode], "display mode", self.current_display().mode_str()),
+			Control::new(&[Action::YRangeUp, Action::YRangeDown], "Y range", format!("{:.2}x", self.graph.scale)),
+			Control::new(&[Action::XRangeUp, Action::XRangeDown], "X range", match self.current_display().division(&self.graph, Dimension::X) {
+				Some(division) => format!("{}/div, {} samples", division, self.graph.samples),
+				None => format!("{} samples", self.graph.samples),
+			}),
+			Control::action(Action::Reset, "reset view"),
+			Control::toggle(Action::Scatter, "scatter", self.graph.scatter),
+			Control::toggle(Action::References, "reference lines", self.graph.references),
+			Control::toggle(Action::Graticule, "graticule", self.graph.graticule),
+			Control::toggle(Action::ToggleUi, "interface", self.graph.show_ui),
+			Control::new(&[Action::Theme], "theme", &self.themes[self.theme].0),
+			Control::new(&[Action::Snapshot], "snapshot", self.snapshot_dir.display()),
+			Control::new(&[Action::Cursor], "add or select cursor", if self.cursors.is_empty() { "none".into() } else { format!("{}", self.cursors.selected + 1) }),
+			Control::new(&[Action::CursorForward, Action::CursorBack], "move cursor (or middle click)", ""),
+			Control::action(Action::ClearCursors, "clear cursors"),
+			Control::toggle(Action::Persistence, "persistence (oscillo, vector)", self.persistence.enabled),
+			Control::new(&[Action::DecayUp, Action::DecayDown], "persistence decay", format!("{:.2}s", self.persistence.decay)),
+			Control::toggle(Action::InfinitePersistence, "infinite persistence", self.persistence.infinite),
+			Control::toggle(Action::Heatmap, "heatmap (oscillo, vector)", self.persistence.heatmap),
+			Control::new(&[Action::Colormap], "heatmap colormap", self.persistence.colormap),
+			Control::toggle(Action::Measurements, "measurements", self.measurements),
+			Control::new(&[Action::MeasureAverageUp, Action::MeasureAverageDown], "measurements averaging", format!("{} frames", self.averager.frames)),
+		]
+	}
+
+	fn cycle_theme(&mut self) {
+		self.theme = (self.theme + 1) % self.themes.len();
+		let (name, theme) = &self.themes[self.theme];
+		theme.apply(&mut self.graph);
+		self.set_status(format!("theme: {}", name));
+	}
+
+	/// take parts marked as changed from given settings, while running. source options are
+	/// left alone, see [App::set_stream]
+	pub fn reconfigure(&mut self, settings: AppBuilder, changes: Changes) {
+		if changes.ui {
+			self.graph.scale = settings.graph.scale;
+			self.graph.references = settings.graph.references;
+			self.graph.graticule = settings.graph.graticule;
+			self.graph.show_ui = settings.graph.show_ui;
+			self.graph.scatter = settings.graph.scatter;
+			self.graph.marker_type = settings.graph.marker_type;
+			if let Some(size) = settings.snapshot_size { self.snapshot_size = size }
+			if let Some(dir) = settings.snapshot_dir { self.snapshot_dir = dir }
+			self.measurements = settings.measurements;
+			self.averager.frames = settings.averager.frames;
+			self.persistence.enabled = settings.persistence.enabled;
+			self.persistence.infinite = settings.persistence.infinite;
+			if !settings.persistence.infinite { self.persistence.decay = settings.persistence.decay }
+			self.persistence.heatmap = settings.persistence.heatmap;
+			self.persistence.colormap = settings.persistence.colormap;
+		}
+		if changes.mode {
+			self.mode = settings.mode;
+		}
+		if changes.themes || changes.theme {
+			let themes = settings.themes.filter(|x| !x.is_empty()).unwrap_or_else(Theme::builtin);
+			let name = match changes.theme {
+				true => settings.theme,
+				false => Some(self.themes[self.theme].0.clone()),
+			};
+			self.themes = themes;
+			self.theme = self.themes.iter().position(|(x, _)| Some(x) == name.as_ref()).unwrap_or(0);
+			self.themes[self.theme].1.apply(&mut self.graph);
+		}
+		if changes.keymap {
+			self.keymap = settings.keymap;
+		}
+		if changes.oscilloscope {
+			self.oscilloscope = settings.oscilloscope.unwrap_or_default();
+		}
+		if changes.vectorscope {
+			self.vectorscope = settings.vectorscope.unwrap_or_default();
+		}
+		if changes.spectroscope {
+			let spectroscope = settings.spectroscope.unwrap_or_default();
+			self.spectroscope.average = spectroscope.average;
+			self.spectroscope.window = spectroscope.window;
+			self.spectroscope.log_y = spectroscope.log_y;
+			self.spectroscope.grid = spectroscope.grid;
+		}
 	}
 
 	fn current_display_mut(&mut self) -> &mut dyn DisplayMode {
@@ -129,47 +687,73 @@ impl App {
 		}
 	}
 
-	fn process_events(&mut self, event: Event) -> Result<bool, io::Error> {
-		let mut quit = false;
-		if let Event::Key(key) = event {
-			if let KeyModifiers::CONTROL = key.modifiers {
-				match key.code { // mimic other programs shortcuts to quit, for user friendlyness
-					KeyCode::Char('c') | KeyCode::Char('q') | KeyCode::Char('w') => quit = true,
-					_ => {},
+	/// apply a global action, returns true if app should quit
+	fn perform(&mut self, action: Action, magnitude: f64) -> bool {
+		match action {
+			Action::YRangeUp   => update_value_f(&mut self.graph.scale,  0.01, magnitude, 0.0..10.0), // inverted to act as zoom
+			Action::YRangeDown => update_value_f(&mut self.graph.scale, -0.01, magnitude, 0.0..10.0), // inverted to act as zoom
+			Action::XRangeUp | Action::XRangeDown => {
+				let mut graph = self.graph.clone(); // display borrows self
+				self.current_display().range_x(&mut graph, action == Action::XRangeUp, magnitude);
+				self.graph = graph;
+			},
+			Action::Quit       => return true,
+			Action::Help       => self.help           = !self.help,
+			Action::Pause      => self.graph.pause        = !self.graph.pause,
+			Action::Scatter    => self.graph.scatter      = !self.graph.scatter,
+			Action::ToggleUi   => self.graph.show_ui      = !self.graph.show_ui,
+			Action::References => self.graph.references   = !self.graph.references,
+			Action::Graticule  => self.graph.graticule    = !self.graph.graticule,
+			Action::Snapshot   => self.take_snapshot(),
+			Action::Theme      => self.cycle_theme(),
+			Action::Cursor | Action::CursorForward | Action::CursorBack => {
+				let mut cursors = self.cursors.clone(); // display borrows self
+				match action {
+					Action::Cursor       => cursors.next(self.current_display(), &self.graph),
+					Action::CursorForward => cursors.shift(self.current_display(), &self.graph, 0.01 * magnitude),
+					_                    => cursors.shift(self.current_display(), &self.graph, -0.01 * magnitude),
 				}
-			}
-			let magnitude = match key.modifiers {
-				KeyModifiers::SHIFT => 10.0,
-				KeyModifiers::CONTROL => 5.0,
-				KeyModifiers::ALT => 0.2,
-				_ => 1.0,
-			};
-			match key.code {
-				KeyCode::Up       => update_value_f(&mut self.graph.scale,  0.01, magnitude, 0.0..10.0), // inverted to act as zoom
-				KeyCode::Down     => update_value_f(&mut self.graph.scale, -0.01, magnitude, 0.0..10.0), // inverted to act as zoom
-				KeyCode::Right    => update_value_i(&mut self.graph.samples, true, 25, magnitude, 0..self.graph.width*2),
-				KeyCode::Left     => update_value_i(&mut self.graph.samples, false, 25, magnitude, 0..self.graph.width*2),
-				KeyCode::Char('q') => quit = true,
-				KeyCode::Char(' ') => self.graph.pause        = !self.graph.pause,
-				KeyCode::Char('s') => self.graph.scatter      = !self.graph.scatter,
-				KeyCode::Char('h') => self.graph.show_ui      = !self.graph.show_ui,
-				KeyCode::Char('r') => self.graph.references   = !self.graph.references,
-				KeyCode::Tab => { // switch modes
-					match self.mode {
-						CurrentDisplayMode::Oscilloscope => self.mode = CurrentDisplayMode::Vectorscope,
-						CurrentDisplayMode::Vectorscope => self.mode = CurrentDisplayMode::Spectroscope,
-						CurrentDisplayMode::Spectroscope => self.mode = CurrentDisplayMode::Oscilloscope,
-					}
-				},
-				KeyCode::Esc => {
-					self.graph.samples = self.graph.width;
-					self.graph.scale = 1.;
-				},
-				_ => {},
-			}
-		};
-	
-		Ok(quit)
+				self.cursors = cursors;
+			},
+			Action::ClearCursors => self.cursors.clear(),
+			Action::Measurements => {
+				self.measurements = !self.measurements;
+				self.averager.clear();
+			},
+			Action::Persistence => {
+				self.persistence.enabled = !self.persistence.enabled;
+				self.persistence.clear();
+			},
+			Action::InfinitePersistence => {
+				self.persistence.infinite = !self.persistence.infinite;
+				self.persistence.clear();
+			},
+			Action::Heatmap => {
+				self.persistence.heatmap = !self.persistence.heatmap;
+				self.persistence.clear();
+			},
+			Action::Colormap   => self.persistence.colormap = self.persistence.colormap.next(),
+			Action::DecayUp    => update_value_f(&mut self.persistence.decay, 0.05, magnitude, 0.01..10.0),
+			Action::DecayDown  => update_value_f(&mut self.persistence.decay, -0.05, magnitude, 0.01..10.0),
+			Action::MeasureAverageUp   => update_value_i(&mut self.averager.frames, true, 1, magnitude, 1..1000),
+			Action::MeasureAverageDown => update_value_i(&mut self.averager.frames, false, 1, magnitude, 1..1000),
+			Action::NextMode   => { // switch modes, cursors don't mean the same thing in others
+				self.cursors.clear();
+				self.persistence.clear();
+				match self.mode {
+					CurrentDisplayMode::Oscilloscope => self.mode = CurrentDisplayMode::Vectorscope,
+					CurrentDisplayMode::Vectorscope => self.mode = CurrentDisplayMode::Spectroscope,
+					CurrentDisplayMode::Spectroscope => self.mode = CurrentDisplayMode::Oscilloscope,
+				}
+			},
+			Action::Reset => {
+				self.graph.samples = self.graph.width;
+				self.graph.scale = 1.;
+				self.graph.offset = [0.0, 0.0];
+			},
+			_ => {},
+		}
+		false
 	}
 }
 
@@ -201,30 +785,187 @@ pub fn update_value_i(val: &mut u32, inc: bool, base: u32, magnitude: f64, range
 	}
 }
 
-fn make_header<'a>(cfg: &GraphConfig, module_header: &'a str, kind_o_scope: &'static str, fps: usize, pause: bool) -> Table<'a> {
+/// popup listing global and mode controls, with keys currently bound to them. also returns its size
+fn make_help<'a>(cfg: &GraphConfig, keymap: &Keymap, global: Vec<Control>, display: &dyn DisplayMode) -> (Table<'a>, u16, u16) {
+	let title = |txt: String| Row::new(vec![Cell::from(txt).style(Style::default().fg(cfg.palette(0)).add_modifier(Modifier::BOLD))]);
+	let lines = |controls: Vec<Control>| controls.into_iter()
+		.map(|x| [keymap.describe(&x.actions), x.name.to_string(), x.value])
+		.filter(|x| !x[0].is_empty()) // unbound
+		.collect::<Vec<[String; 3]>>();
+	let m = &keymap.multipliers;
+	let mut global = lines(global);
+	global.push(["shift / ctrl / alt".into(), "scale increments".into(), format!("x{} / x{} / x{}", m.shift, m.ctrl, m.alt)]);
+	global.push(["scroll / shift+scroll".into(), "zoom Y / X".into(), String::new()]);
+	global.push(["left drag".into(), "pan".into(), format!("{:.2} / {:.2}", cfg.offset[0], cfg.offset[1])]);
+	global.push(["right drag".into(), "zoom on area".into(), String::new()]);
+	let mode = lines(display.controls());
+
+	let mut widths = [0; 3];
+	for line in global.iter().chain(mode.iter()) {
+		for (w, txt) in widths.iter_mut().zip(line.iter()) {
+			*w = (*w).max(txt.chars().count() as u16);
+		}
+	}
+
+	let mut rows = vec![title("global".into())];
+	rows.extend(global.into_iter().map(Row::new));
+	if !mode.is_empty() {
+		rows.push(Row::new(vec![""]));
+		rows.push(title(display.mode_str().into()));
+		rows.extend(mode.into_iter().map(Row::new));
+	}
+
+	let height = rows.len() as u16 + 2; // borders
+	let width = widths.iter().sum::<u16>() + 2 + 2; // borders and column spacing
+	let table = Table::new(rows, widths.map(Constraint::Length))
+		.block(
+			Block::default()
+				.title(" help ")
+				.borders(Borders::ALL)
+				.border_style(Style::default().fg(cfg.axis_color))
+		)
+		.style(Style::default().fg(cfg.labels_color));
+	(table, width, height)
+}
+
+/// side panel with cursor readouts, highlighting lines of selected cursor. also returns its size
+fn make_readout<'a>(cfg: &GraphConfig, readout: Vec<Readout>, selected: usize) -> (Table<'a>, u16, u16) {
+	let height = readout.len() as u16 + 2; // borders
+	let label = readout.iter().map(|x| x.label.chars().count()).max().unwrap_or(0) as u16;
+	let value = readout.iter().map(|x| x.value.chars().count()).max().unwrap_or(0) as u16;
+	let rows = readout.into_iter().map(|x| {
+		let style = if x.cursor == Some(selected) { Style::default().fg(cfg.palette(0)).add_modifier(Modifier::BOLD) } else { Style::default() };
+		Row::new(vec![x.label, x.value]).style(style)
+	});
+	let table = Table::new(rows, [Constraint::Length(label), Constraint::Length(value)])
+		.block(
+			Block::default()
+				.title(" cursors ")
+				.borders(Borders::ALL)
+				.border_style(Style::default().fg(cfg.axis_color))
+		)
+		.style(Style::default().fg(cfg.labels_color));
+	(table, (label + value + 1 + 2).max(11), height) // column spacing, borders, and title must fit
+}
+
+/// side panel with a column of measurements for each channel. also returns its size
+fn make_measurements<'a>(cfg: &GraphConfig, measured: &[Measurement], display: &dyn DisplayMode) -> (Table<'a>, u16, u16) {
+	let channels = measured.iter().map(|x| x.values.len()).max().unwrap_or(0);
+	let mut lines = vec![std::iter::once(String::new()).chain((0..channels).map(|i| display.channel_name(i))).collect::<Vec<String>>()];
+	for m in measured {
+		lines.push(std::iter::once(m.name.to_string()).chain((0..channels).map(|i| m.format(i))).collect());
+	}
+	let mut widths = vec![0; channels + 1];
+	for line in &lines {
+		for (w, txt) in widths.iter_mut().zip(line) {
+			*w = (*w).max(txt.chars().count() as u16);
+		}
+	}
+	let height = lines.len() as u16 + 2; // borders
+	let width = widths.iter().sum::<u16>() + channels as u16 + 2; // column spacing and borders
+	let mut rows = lines.into_iter().map(Row::new);
+	let header = rows.next().unwrap_or_default().style(Style::default().fg(cfg.palette(0)).add_modifier(Modifier::BOLD));
+	let table = Table::new(rows, widths.into_iter().map(Constraint::Length))
+		.header(header)
+		.block(
+			Block::default()
+				.title(" measurements ")
+				.borders(Borders::ALL)
+				.border_style(Style::default().fg(cfg.axis_color))
+		)
+		.style(Style::default().fg(cfg.labels_color));
+	(table, width.max(16), height) // title must fit
+}
+
+/// area of given size in the middle of another one, shrunk if it doesn't fit
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+	let (width, height) = (width.min(area.width), height.min(area.height));
+	Rect {
+		x: area.x + (area.width - width) / 2,
+		y: area.y + (area.height - height) / 2,
+		width,
+		height,
+	}
+}
+
+/// header line with a status message in place of display settings
+fn make_status<'a>(cfg: &GraphConfig, msg: &'a str, kind_o_scope: &'static str) -> Table<'a> {
 	Table::new(
 		vec![
 			Row::new(
 				vec![
-					Cell::from(format!("{}::scope-tui", kind_o_scope)).style(Style::default().fg(*cfg.palette.first().expect("empty palette?")).add_modifier(Modifier::BOLD)),
-					Cell::from(module_header),
-					Cell::from(format!("-{:.2}x+", cfg.scale)),
-					Cell::from(format!("{}/{} spf", cfg.samples, cfg.width)),
-					Cell::from(format!("{}fps", fps)),
-					Cell::from(if cfg.scatter { "***" } else { "---" }),
-					Cell::from(if pause { "||" } else { "|>" }),
+					Cell::from(format!("{}::scope-tui", kind_o_scope)).style(Style::default().fg(cfg.palette(0)).add_modifier(Modifier::BOLD)),
+					Cell::from(msg),
 				]
 			)
 		],
 		vec![
 			Constraint::Percentage(35),
-			Constraint::Percentage(25),
-			Constraint::Percentage(7),
-			Constraint::Percentage(13),
-			Constraint::Percentage(6),
-			Constraint::Percentage(6),
-			Constraint::Percentage(6)
+			Constraint::Percentage(65),
 		]
 	)
 	.style(Style::default().fg(cfg.labels_color))
 }
+
+/// units per division along X and Y, when graticule is shown
+fn divisions(display: &dyn DisplayMode, cfg: &GraphConfig) -> Option<String> {
+	if !cfg.graticule { return None }
+	let divisions : Vec<String> = [Dimension::X, Dimension::Y].into_iter().filter_map(|d| display.division(cfg, d)).collect();
+	Some(format!("{}/div", divisions.join(" ")))
+}
+
+/// header line. with graticule, units per division take the place of scale and samples
+fn make_header<'a>(cfg: &GraphConfig, module_header: &'a str, kind_o_scope: &'static str, fps: usize, pause: bool, divisions: Option<String>) -> Table<'a> {
+	let mut cells = vec![
+		Cell::from(format!("{}::scope-tui", kind_o_scope)).style(Style::default().fg(cfg.palette(0)).add_modifier(Modifier::BOLD)),
+		Cell::from(module_header),
+	];
+	let mut widths = vec![
+		Constraint::Percentage(25), // room for trigger status
+		Constraint::Percentage(35),
+	];
+	match divisions {
+		Some(divisions) => {
+			cells.push(Cell::from(divisions));
+			widths.push(Constraint::Percentage(20));
+		},
+		None => {
+			cells.push(Cell::from(format!("-{:.2}x+", cfg.scale)));
+			cells.push(Cell::from(format!("{}/{} spf", cfg.samples, cfg.width)));
+			widths.extend([Constraint::Percentage(7), Constraint::Percentage(13)]);
+		},
+	}
+	cells.push(Cell::from(format!("{}fps", fps)));
+	cells.push(Cell::from(if cfg.scatter { "***" } else { "---" }));
+	cells.push(Cell::from(if pause { "||" } else { "|>" }));
+	widths.extend([Constraint::Percentage(6), Constraint::Percentage(6), Constraint::Percentage(6)]);
+	Table::new(vec![Row::new(cells)], widths)
+		.style(Style::default().fg(cfg.labels_color))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn themes() -> Vec<(String, Theme)> {
+		vec![
+			("red".into(), Theme { palette: vec![Color::Red], ..Theme::dark() }),
+			("blue".into(), Theme { palette: vec![Color::Blue], ..Theme::dark() }),
+		]
+	}
+
+	#[test]
+	fn theme_is_found_whatever_order_themes_come_in() {
+		for app in [App::builder().theme("blue").themes(themes()).build(), App::builder().themes(themes()).theme("blue").build()] {
+			assert_eq!(app.themes[app.theme].0, "blue");
+			assert_eq!(app.graph.palette, vec![Color::Blue]);
+		}
+	}
+
+	#[test]
+	fn unknown_theme_keeps_graph_colors() {
+		let app = App::builder().palette(vec![Color::Green]).themes(themes()).theme("nope").build();
+		assert_eq!(app.theme, 0);
+		assert_eq!(app.graph.palette, vec![Color::Green]);
+	}
+}
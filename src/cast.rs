@@ -0,0 +1,91 @@
+use std::{cell::Cell, fs::File, io::{self, BufWriter, Write}, path::Path, rc::Rc, time::{Instant, SystemTime, UNIX_EPOCH}};
+
+use scope_tui::{DataSource, Matrix, Size};
+
+/// records everything written through it as an asciinema v2 .cast file, passing it on to inner
+/// writer. every flush becomes one output event, so a full rendered frame ends up in one event
+pub struct CastRecorder<W: Write> {
+	inner: W,
+	cast: BufWriter<File>,
+	pending: Vec<u8>,
+	clock: Clock,
+}
+
+/// where event timestamps come from
+pub enum Clock {
+	/// real time since recording started
+	Wall(Instant),
+	/// audio time consumed from source, for recordings not happening in real time
+	Stream(StreamClock),
+}
+
+impl Clock {
+	fn now(&self) -> f64 {
+		match self {
+			Clock::Wall(start) => start.elapsed().as_secs_f64(),
+			Clock::Stream(clock) => clock.0.get(),
+		}
+	}
+}
+
+impl<W: Write> CastRecorder<W> {
+	pub fn new(inner: W, path: &Path, size: Size, clock: Clock) -> io::Result<Self> {
+		let mut cast = BufWriter::new(File::create(path)?);
+		let header = serde_json::json!({
+			"version": 2,
+			"width": size.width,
+			"height": size.height,
+			"timestamp": SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0),
+			"env": { "TERM": std::env::var("TERM").unwrap_or("xterm-256color".into()) },
+		});
+		writeln!(cast, "{}", header)?;
+		cast.flush()?;
+		Ok(CastRecorder { inner, cast, clock, pending: Vec::new() })
+	}
+}
+
+impl<W: Write> Write for CastRecorder<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let n = self.inner.write(buf)?;
+		self.pending.extend_from_slice(&buf[..n]);
+		Ok(n)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()?;
+		if !self.pending.is_empty() {
+			let data = String::from_utf8_lossy(&self.pending);
+			writeln!(self.cast, "[{:.6}, \"o\", {}]", self.clock.now(), serde_json::to_string(&data)?)?;
+			self.cast.flush()?; // keep file valid even if we get killed
+			self.pending.clear();
+		}
+		Ok(())
+	}
+}
+
+/// seconds of audio received so far, shared between a [ClockedSource] and a recorder
+#[derive(Debug, Clone, Default)]
+pub struct StreamClock(Rc<Cell<f64>>);
+
+/// advances given clock by the duration of every chunk received from inner source
+pub struct ClockedSource {
+	source: Box<dyn DataSource<f64>>,
+	clock: StreamClock,
+	sample_rate: f64,
+}
+
+impl ClockedSource {
+	#[allow(clippy::new_ret_no_self)]
+	pub fn new(source: Box<dyn DataSource<f64>>, clock: StreamClock, sample_rate: u32) -> Box<dyn DataSource<f64>> {
+		Box::new(ClockedSource { source, clock, sample_rate: sample_rate as f64 })
+	}
+}
+
+impl DataSource<f64> for ClockedSource {
+	fn recv(&mut self) -> Option<Matrix<f64>> {
+		let data = self.source.recv()?;
+		let samples = data.first().map(|x| x.len()).unwrap_or(0);
+		self.clock.0.set(self.clock.0.get() + samples as f64 / self.sample_rate);
+		Some(data)
+	}
+}
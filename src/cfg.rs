@@ -1,6 +1,15 @@
-use clap::{Parser, Subcommand};
+use std::{collections::BTreeMap, path::{Path, PathBuf}, time::{Duration, Instant, SystemTime}};
 
-use crate::music::Note;
+use clap::{Arg, ArgAction, CommandFactory, FromArgMatches, Parser, Subcommand};
+use serde::Deserialize;
+
+use ratatui::symbols::Marker;
+use scope_tui::{
+	keymap::{Action, Keymap, Keys, Multipliers}, app::Changes, display::{oscilloscope::Oscilloscope, persistence::Colormap, spectroscope::Spectroscope, vectorscope::Vectorscope},
+	music::Note, App, AppBuilder, CurrentDisplayMode, GraphConfig, Size, StreamOptions, Theme,
+};
+
+use crate::render::RenderFormat;
 
 const HELP_TEMPLATE : &str = "{before-help}\
 {name} {version} -- by {author}
@@ -12,21 +21,87 @@ const HELP_TEMPLATE : &str = "{before-help}\
 ";
 
 /// a simple oscilloscope/vectorscope for your terminal
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None, help_template = HELP_TEMPLATE)]
 pub struct ScopeArgs {
 	#[clap(subcommand)]
-	pub source: ScopeSource,
-	
+	pub command: Option<ScopeCommand>,
+
 	#[command(flatten)]
 	pub opts: SourceOptions,
 
 	#[command(flatten)]
 	pub ui: UiOptions,
+
+	/// render one frame to given file (.svg or .png) and exit, without starting the interface
+	#[arg(long, value_name = "FILE")]
+	pub snapshot: Option<PathBuf>,
+
+	/// record rendered output to given asciinema v2 file (.cast), see `render` for headless recordings
+	#[arg(long, value_name = "FILE")]
+	pub record: Option<PathBuf>,
+
+	/// configuration file to load, instead of $XDG_CONFIG_HOME/scope-tui/config.toml
+	#[arg(long, value_name = "FILE")]
+	pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ScopeCommand {
+	/// render every frame from a source without a terminal, until it runs out
+	Render {
+		#[command(flatten)]
+		opts: RenderOptions,
+
+		#[clap(subcommand)]
+		source: Option<ScopeSource>,
+	},
+
+	/// read a source and print a report with statistics of every channel
+	Analyze {
+		#[command(flatten)]
+		opts: AnalyzeOptions,
+
+		#[clap(subcommand)]
+		source: Option<ScopeSource>,
+	},
+
+	#[command(flatten)]
+	Source(ScopeSource),
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct AnalyzeOptions {
+	/// how many seconds to read from source, defaults to reading until it runs out
+	#[arg(short, long, value_name = "SECS")]
+	pub duration: Option<f64>,
+
+	/// print report as json
+	#[arg(long, default_value_t = false)]
+	pub json: bool,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct RenderOptions {
+	/// output format for rendered frames
+	#[arg(long, value_enum, default_value_t = RenderFormat::Text)]
+	pub format: RenderFormat,
+
+	/// where to write frames: a file for text and cast (text defaults to stdout), a directory for images
+	#[arg(short, long, value_name = "PATH")]
+	pub output: Option<PathBuf>,
+
+	/// frame size in columns and rows, for text and cast (images use --snapshot-size)
+	#[arg(long, value_name = "COLSxROWS", default_value_t = Size { width: 80, height: 24 })]
+	pub size: Size,
 }
 
 #[derive(Debug, Clone, Parser)]
 pub struct UiOptions {
+	/// display mode to start in
+	#[arg(long, value_enum, default_value_t = CurrentDisplayMode::Oscilloscope)]
+	pub mode: CurrentDisplayMode,
+
 	/// floating point vertical scale, from 0 to 1
 	#[arg(short, long, value_name = "x", default_value_t = 1.0)]
 	pub scale: f32,
@@ -39,6 +114,10 @@ pub struct UiOptions {
 	#[arg(long, default_value_t = false)]
 	pub no_reference: bool,
 
+	/// draw a grid of scope-style divisions over the plot
+	#[arg(long, default_value_t = false)]
+	pub graticule: bool,
+
 	/// hide UI and only draw waveforms
 	#[arg(long, default_value_t = false)]
 	pub no_ui: bool,
@@ -46,9 +125,46 @@ pub struct UiOptions {
 	/// don't use braille dots for drawing lines
 	#[arg(long, default_value_t = false)]
 	pub no_braille: bool,
+
+	/// show live measurements of each channel beside the plot
+	#[arg(long, default_value_t = false)]
+	pub measurements: bool,
+
+	/// average measurements over this many frames
+	#[arg(long, value_name = "FRAMES", default_value_t = 1)]
+	pub measure_average: u32,
+
+	/// draw oscilloscope and vectorscope traces with persistence, like phosphor on a CRT
+	#[arg(long, default_value_t = false)]
+	pub persistence: bool,
+
+	/// seconds for persistence to fade to about a third, "inf" to never fade
+	#[arg(long, value_name = "SECONDS", default_value_t = 0.5)]
+	pub decay: f64,
+
+	/// draw oscilloscope and vectorscope traces as heatmap of how often each spot is hit
+	#[arg(long, default_value_t = false)]
+	pub heatmap: bool,
+
+	/// colors of heatmap intensity
+	#[arg(long, value_enum, value_name = "NAME", default_value_t = Colormap::Viridis)]
+	pub colormap: Colormap,
+
+	/// color theme: dark, light, colorblind, monochrome, phosphor or one from configuration file
+	#[arg(long, value_name = "NAME", default_value = "dark")]
+	pub theme: String,
+
+	/// resolution of snapshot images, in pixels
+	#[arg(long, value_name = "WxH", default_value_t = Size { width: 1920, height: 1080 })]
+	pub snapshot_size: Size,
+
+	/// directory where snapshots taken while running are saved
+	#[arg(long, value_name = "DIR", default_value = ".")]
+	pub snapshot_dir: PathBuf,
 }
 
-#[derive(Debug, Clone, Subcommand)]
+#[derive(Debug, Clone, Subcommand, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
 pub enum ScopeSource {
 
 	#[cfg(feature = "pulseaudio")]
@@ -58,7 +174,8 @@ pub enum ScopeSource {
 		device: Option<String>,
 
 		/// PulseAudio server buffer size, in block number
-		#[arg(long, value_name = "N", default_value_t = 32)]
+		#[arg(long, value_name = "N", default_value_t = default_server_buffer())]
+		#[serde(default = "default_server_buffer")]
 		server_buffer: u32,
 	},
 
@@ -69,6 +186,7 @@ pub enum ScopeSource {
 
 		/// limit data flow to match requested sample rate (UNIMPLEMENTED)
 		#[arg(short, long, default_value_t = false)]
+		#[serde(default)]
 		limit_rate: bool,
 	},
 
@@ -78,11 +196,16 @@ pub enum ScopeSource {
 		device: Option<String>,
 
 		/// timeout (in seconds) waiting for audio stream
-		#[arg(long, default_value_t = 60)]
+		#[arg(long, default_value_t = default_timeout())]
+		#[serde(default = "default_timeout")]
 		timeout: u64,
 	}
 }
 
+#[cfg(feature = "pulseaudio")]
+fn default_server_buffer() -> u32 { 32 }
+fn default_timeout() -> u64 { 60 }
+
 #[derive(Debug, Clone, Parser)]
 pub struct SourceOptions {
 	/// number of channels to open
@@ -104,6 +227,10 @@ pub struct SourceOptions {
 
 // TODO its convenient to keep this here but it's not really the best place...
 impl SourceOptions {
+	pub fn stream(&self) -> StreamOptions {
+		StreamOptions { channels: self.channels, buffer: self.buffer, sample_rate: self.sample_rate }
+	}
+
 	pub fn tune(&mut self) {
 		if let Some(txt) = &self.tune { // TODO make it less jank
 			if let Ok(note) = txt.parse::<Note>() {
@@ -117,3 +244,279 @@ impl SourceOptions {
 		}
 	}
 }
+
+/// settings loaded from configuration file. cli options always take precedence
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	/// defaults for ui options, by option name
+	pub ui: toml::Table,
+	/// defaults for source options, by option name
+	pub options: toml::Table,
+	/// source to use when none is given on command line
+	pub source: Option<ScopeSource>,
+	/// user defined themes, by name
+	pub themes: BTreeMap<String, Theme>,
+	/// keys bound to actions, replacing default ones
+	pub keys: BTreeMap<Action, Keys>,
+	/// how much shift, ctrl and alt scale increments
+	pub multipliers: Multipliers,
+	/// initial state of display modes
+	pub oscilloscope: Oscilloscope,
+	pub vectorscope: Vectorscope,
+	pub spectroscope: Spectroscope,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+	#[error("could not read {0}: {1}")]
+	Io(PathBuf, std::io::Error),
+
+	#[error("invalid configuration in {0}: {1}")]
+	Toml(PathBuf, toml::de::Error),
+
+	#[error("unknown option '{0}' in configuration")]
+	UnknownOption(String),
+
+	#[error("option '{0}' in configuration must be a string, number or boolean")]
+	InvalidValue(String),
+
+	#[error("unknown theme '{0}'")]
+	UnknownTheme(String),
+
+	#[error("{0}")]
+	Cli(#[from] clap::Error),
+}
+
+impl Config {
+	/// default configuration file, following XDG base directory spec
+	pub fn default_path() -> Option<PathBuf> {
+		std::env::var_os("XDG_CONFIG_HOME")
+			.map(PathBuf::from)
+			.filter(|x| x.is_absolute())
+			.or_else(|| std::env::var_os("HOME").map(|x| PathBuf::from(x).join(".config")))
+			.map(|x| x.join("scope-tui").join("config.toml"))
+	}
+
+	pub fn load(path: &Path) -> Result<Self, ConfigError> {
+		Ok(Config::load_table(path)?.1)
+	}
+
+	/// raw table and parsed config, so that changed sections can be found on reload
+	fn load_table(path: &Path) -> Result<(toml::Table, Self), ConfigError> {
+		let txt = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.into(), e))?;
+		let table : toml::Table = txt.parse().map_err(|e| ConfigError::Toml(path.into(), e))?;
+		let config = Config::deserialize(table.clone()).map_err(|e| ConfigError::Toml(path.into(), e))?;
+		Ok((table, config))
+	}
+
+	/// built-in themes followed by user defined ones, which can also replace built-in ones
+	pub fn themes(&self) -> Vec<(String, Theme)> {
+		let mut themes = Theme::builtin();
+		for (name, theme) in &self.themes {
+			match themes.iter_mut().find(|(x, _)| x == name) {
+				Some((_, builtin)) => *builtin = theme.clone(),
+				None => themes.push((name.clone(), theme.clone())),
+			}
+		}
+		themes
+	}
+
+	pub fn keymap(&self) -> Keymap {
+		Keymap::with(&self.keys, self.multipliers)
+	}
+
+	/// file passed with --config, or default one if it exists
+	pub fn path_from_env() -> Option<PathBuf> {
+		let mut args = std::env::args_os().skip(1); // need to find it before actually parsing args
+		while let Some(arg) = args.next() {
+			if arg == "--config" {
+				return args.next().map(PathBuf::from);
+			} else if let Some(path) = arg.to_str().and_then(|x| x.strip_prefix("--config=")) {
+				return Some(path.into());
+			}
+		}
+		Config::default_path().filter(|x| x.exists())
+	}
+
+	/// load file passed with --config, or default one if it exists
+	pub fn from_env() -> Result<Self, ConfigError> {
+		match Config::path_from_env() {
+			Some(path) => Config::load(&path),
+			None => Ok(Config::default()),
+		}
+	}
+}
+
+/// how often configuration file is checked for changes
+const WATCH_INTERVAL : Duration = Duration::from_millis(500);
+
+/// keeps track of configuration file while running, reloading it when it changes.
+/// args are parsed again on every reload so that cli options still take precedence
+pub struct ConfigWatch {
+	path: PathBuf,
+	modified: Option<SystemTime>,
+	last_check: Instant,
+	table: toml::Table,
+	pub args: ScopeArgs,
+	pub config: Config,
+}
+
+impl ConfigWatch {
+	pub fn new(path: PathBuf, args: ScopeArgs, config: Config) -> Self {
+		let modified = std::fs::metadata(&path).and_then(|x| x.modified()).ok();
+		let table = Config::load_table(&path).map(|x| x.0).unwrap_or_default();
+		ConfigWatch { path, modified, table, args, config, last_check: Instant::now() }
+	}
+
+	/// if file changed since last check, reload it and return names of changed sections.
+	/// on errors, previous args and config are kept
+	pub fn poll(&mut self) -> Option<Result<Vec<String>, ConfigError>> {
+		if self.last_check.elapsed() < WATCH_INTERVAL { return None }
+		self.last_check = Instant::now();
+		let modified = std::fs::metadata(&self.path).and_then(|x| x.modified()).ok();
+		if modified.is_none() || modified == self.modified { return None }
+		self.modified = modified;
+		Some(self.reload())
+	}
+
+	fn reload(&mut self) -> Result<Vec<String>, ConfigError> {
+		let (table, config) = Config::load_table(&self.path)?;
+		let mut args = ScopeArgs::try_parse_with(&config)?;
+		args.opts.tune();
+		let changed = table.keys().chain(self.table.keys())
+			.filter(|key| table.get(*key) != self.table.get(*key))
+			.cloned()
+			.collect::<std::collections::BTreeSet<String>>() // dedup
+			.into_iter()
+			.collect();
+		self.table = table;
+		self.args = args;
+		self.config = config;
+		Ok(changed)
+	}
+
+	/// apply changes to configuration file to app, if any. returns true if capture should restart
+	pub fn apply(&mut self, app: &mut App) -> bool {
+		let previous = self.args.ui.clone();
+		let changed = match self.poll() {
+			None => return false,
+			Some(Ok(changed)) => changed,
+			Some(Err(e)) => {
+				app.set_status(format!("config not reloaded: {}", e));
+				return false;
+			},
+		};
+		let changed = |section: &str| changed.iter().any(|x| x == section);
+		app.reconfigure(self.args.app(&self.config), Changes {
+			ui: changed("ui"),
+			mode: self.args.ui.mode != previous.mode,
+			theme: self.args.ui.theme != previous.theme,
+			themes: changed("themes"),
+			keymap: changed("keys") || changed("multipliers"),
+			oscilloscope: changed("oscilloscope"),
+			vectorscope: changed("vectorscope"),
+			spectroscope: changed("spectroscope"),
+		});
+
+		let restart = changed("options") || changed("source");
+		app.set_status(if restart { "config reloaded, restarting capture".into() } else { "config reloaded".into() });
+		restart
+	}
+}
+
+impl ScopeArgs {
+	/// parse cli arguments, using options from configuration file as defaults. exits on cli errors
+	pub fn parse_with(config: &Config) -> Result<Self, ConfigError> {
+		match ScopeArgs::try_parse_with(config) {
+			Err(ConfigError::Cli(e)) => e.exit(),
+			res => res,
+		}
+	}
+
+	pub fn try_parse_with(config: &Config) -> Result<Self, ConfigError> {
+		let mut cmd = ScopeArgs::command();
+		let flags : Vec<String> = cmd.get_arguments()
+			.filter(|x| matches!(x.get_action(), ArgAction::SetTrue))
+			.map(|x| x.get_id().to_string())
+			.collect();
+		for id in flags {
+			cmd = cmd.mut_arg(id, overridable);
+		}
+		let sections = [
+			("ui", &config.ui, UiOptions::command()),
+			("options", &config.options, SourceOptions::command()),
+		];
+		for (section, table, group) in sections {
+			for (key, value) in table {
+				let id = key.replace('-', "_");
+				if !group.get_arguments().any(|x| x.get_id() == id.as_str()) {
+					return Err(ConfigError::UnknownOption(format!("{}.{}", section, key)));
+				}
+				let value = match value {
+					toml::Value::String(x) => x.clone(),
+					toml::Value::Integer(x) => x.to_string(),
+					toml::Value::Float(x) => x.to_string(),
+					toml::Value::Boolean(x) => x.to_string(),
+					_ => return Err(ConfigError::InvalidValue(format!("{}.{}", section, key))),
+				};
+				cmd = cmd.mut_arg(id, |x| x.default_value(value));
+			}
+		}
+		let matches = cmd.try_get_matches()?;
+		let args = ScopeArgs::from_arg_matches(&matches)?;
+		if !config.themes().iter().any(|(name, _)| *name == args.ui.theme) {
+			return Err(ConfigError::UnknownTheme(args.ui.theme));
+		}
+		Ok(args)
+	}
+
+	/// app built from cli options, with defaults, themes, keys and display modes from configuration
+	pub fn app(&self, config: &Config) -> AppBuilder {
+		let ui = &self.ui;
+		App::builder()
+			.graph(GraphConfig {
+				scale: ui.scale as f64,
+				references: !ui.no_reference,
+				graticule: ui.graticule,
+				show_ui: !ui.no_ui,
+				scatter: ui.scatter,
+				marker_type: if ui.no_braille { Marker::Dot } else { Marker::Braille },
+				..Default::default()
+			})
+			.stream(&self.opts.stream())
+			.themes(config.themes())
+			.theme(&ui.theme)
+			.mode(ui.mode)
+			.snapshots(ui.snapshot_size, ui.snapshot_dir.clone())
+			.measurements(ui.measurements, ui.measure_average)
+			.persistence(ui.persistence, ui.decay)
+			.heatmap(ui.heatmap, ui.colormap)
+			.keymap(config.keymap())
+			.oscilloscope(config.oscilloscope.clone())
+			.vectorscope(config.vectorscope.clone())
+			.spectroscope(config.spectroscope.clone())
+	}
+
+	/// source given on command line, if any
+	pub fn source(&self) -> Option<&ScopeSource> {
+		match &self.command {
+			Some(ScopeCommand::Source(source)) => Some(source),
+			Some(ScopeCommand::Render { source, .. } | ScopeCommand::Analyze { source, .. }) => source.as_ref(),
+			None => None,
+		}
+	}
+}
+
+/// flag that also takes an explicit value, like `--scatter=false`, so that it can
+/// be turned off from command line when configuration turns it on
+fn overridable(arg: Arg) -> Arg {
+	arg
+		.action(ArgAction::Set)
+		.num_args(0..=1)
+		.require_equals(true)
+		.default_missing_value("true")
+		.value_name("BOOL")
+		.hide_possible_values(true)
+		.value_parser(clap::value_parser!(bool))
+}
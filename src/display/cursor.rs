@@ -0,0 +1,100 @@
+use ratatui::widgets::GraphType;
+
+use super::{DataSet, Dimension, DisplayMode, GraphConfig};
+
+/// two vertical and two horizontal measurement cursors, in data coordinates.
+/// unset cursors are not shown
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cursors {
+	/// vertical cursors, at X coordinates
+	pub x: [Option<f64>; 2],
+	/// horizontal cursors, at Y coordinates
+	pub y: [Option<f64>; 2],
+	/// which one keys and mouse move: 0 and 1 are vertical, 2 and 3 horizontal
+	pub selected: usize,
+}
+
+impl Cursors {
+	pub fn is_empty(&self) -> bool {
+		self.x.iter().chain(self.y.iter()).all(|x| x.is_none())
+	}
+
+	pub fn clear(&mut self) {
+		*self = Cursors::default();
+	}
+
+	/// dimension along which selected cursor moves
+	pub fn dimension(&self) -> Dimension {
+		if self.selected < 2 { Dimension::X } else { Dimension::Y }
+	}
+
+	fn slot(&mut self) -> &mut Option<f64> {
+		match self.selected {
+			0 | 1 => &mut self.x[self.selected],
+			_ => &mut self.y[self.selected - 2],
+		}
+	}
+
+	/// place selected cursor if not shown yet, otherwise select next one and place that.
+	/// new cursors go at one third or two thirds of the view, so pairs don't overlap
+	pub fn next(&mut self, display: &dyn DisplayMode, cfg: &GraphConfig) {
+		if self.slot().is_some() {
+			self.selected = (self.selected + 1) % 4;
+		}
+		let [a, b] = display.view(cfg, self.dimension());
+		let at = a + (b - a) * (1 + self.selected % 2) as f64 / 3.0;
+		self.slot().get_or_insert(at);
+	}
+
+	/// move selected cursor to given data coordinates, only along its own dimension
+	pub fn place(&mut self, x: f64, y: f64) {
+		let at = if self.dimension() == Dimension::X { x } else { y };
+		*self.slot() = Some(at);
+	}
+
+	/// move selected cursor by a fraction of the view, if it is shown
+	pub fn shift(&mut self, display: &dyn DisplayMode, cfg: &GraphConfig, fraction: f64) {
+		let [a, b] = display.view(cfg, self.dimension());
+		if let Some(at) = self.slot() {
+			*at += (b - a) * fraction;
+		}
+	}
+
+	/// lines across the view for all shown cursors
+	pub fn datasets(&self, display: &dyn DisplayMode, cfg: &GraphConfig) -> Vec<DataSet> {
+		let [x0, x1] = display.view(cfg, Dimension::X);
+		let [y0, y1] = display.view(cfg, Dimension::Y);
+		let line = |data| DataSet::new(None, data, cfg.marker_type, GraphType::Line, cfg.labels_color);
+		self.x.iter().flatten().map(|x| line(vec![(*x, y0), (*x, y1)]))
+			.chain(self.y.iter().flatten().map(|y| line(vec![(x0, *y), (x1, *y)])))
+			.collect()
+	}
+
+	/// difference between the two cursors of a dimension, if both are shown
+	pub fn delta(&self, dimension: Dimension) -> Option<f64> {
+		match dimension {
+			Dimension::X => Some(self.x[1]? - self.x[0]?),
+			Dimension::Y => Some(self.y[1]? - self.y[0]?),
+		}
+	}
+}
+
+/// one line of cursor readouts panel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Readout {
+	pub label: String,
+	pub value: String,
+	/// cursor this line belongs to, highlighted while selected
+	pub cursor: Option<usize>,
+}
+
+impl Readout {
+	pub fn new(label: impl Into<String>, value: impl Into<String>) -> Self {
+		Readout { label: label.into(), value: value.into(), cursor: None }
+	}
+
+	pub fn of(mut self, cursor: usize) -> Self {
+		self.cursor = Some(cursor);
+		self
+	}
+}
@@ -0,0 +1,102 @@
+use std::{f64::consts::PI, ops::Range};
+
+/// samples on each side of an interpolated point that contribute to it
+const TAPS: usize = 16;
+
+/// points per sample when looking for peaks between samples, like true peak meters do
+pub const TRUE_PEAK_FACTOR: usize = 4;
+
+/// band-limited value at given fractional sample index, reconstructed with windowed sin(x)/x
+/// like a DAC would. past the ends, first and last samples are held
+pub fn sinc(samples: &[f64], at: f64) -> f64 {
+	if samples.is_empty() { return 0.0 }
+	let center = at.floor() as isize;
+	let last = samples.len() as isize - 1;
+	let mut out = 0.0;
+	for i in center - TAPS as isize + 1..=center + TAPS as isize {
+		out += samples[i.clamp(0, last) as usize] * kernel(at - i as f64);
+	}
+	out
+}
+
+/// sin(x)/x tapered by a Hann window, so few taps are needed
+fn kernel(x: f64) -> f64 {
+	if x.abs() < 1e-9 { return 1.0 }
+	if x.abs() >= TAPS as f64 { return 0.0 }
+	let window = 0.5 + 0.5 * (PI * x / TAPS as f64).cos();
+	(PI * x).sin() / (PI * x) * window
+}
+
+/// fractional indexes and values of given range of samples, with given points per sample.
+/// last sample of range is included
+pub fn upsample(samples: &[f64], range: Range<usize>, factor: usize) -> Vec<(f64, f64)> {
+	let factor = factor.max(1);
+	let end = range.end.min(samples.len());
+	if range.start >= end { return Vec::new() }
+	let steps = (end - 1 - range.start) * factor;
+	(0..=steps)
+		.map(|k| {
+			let at = range.start as f64 + k as f64 / factor as f64;
+			(at, if k % factor == 0 { samples[range.start + k / factor] } else { sinc(samples, at) })
+		})
+		.collect()
+}
+
+/// highest absolute value of band-limited signal, which can be above any sample when peaks fall between them
+pub fn true_peak(samples: &[f64]) -> Option<f64> {
+	if samples.is_empty() { return None }
+	let peak = upsample(samples, 0..samples.len(), TRUE_PEAK_FACTOR)
+		.into_iter()
+		.fold(0.0, |peak: f64, (_, x)| peak.max(x.abs()));
+	Some(peak)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// sine at given fraction of sampling rate, starting at given phase
+	fn sine(len: usize, freq: f64, phase: f64) -> Vec<f64> {
+		(0..len).map(|i| (2.0 * PI * freq * i as f64 + phase).sin()).collect()
+	}
+
+	#[test]
+	fn sinc_passes_through_samples() {
+		let samples = sine(64, 0.05, 0.3);
+		for (i, x) in samples.iter().enumerate() {
+			assert!((sinc(&samples, i as f64) - x).abs() < 1e-9);
+		}
+	}
+
+	#[test]
+	fn sinc_reconstructs_between_samples() {
+		let samples = sine(256, 0.1, 0.0);
+		for at in [100.25, 120.5, 140.75] {
+			let expected = (2.0 * PI * 0.1 * at).sin();
+			assert!((sinc(&samples, at) - expected).abs() < 0.01, "at {at}");
+		}
+	}
+
+	#[test]
+	fn sinc_holds_ends() {
+		assert_eq!(sinc(&[], 3.0), 0.0);
+		assert!((sinc(&[0.5; 8], -20.0) - 0.5).abs() < 1e-9);
+		assert!((sinc(&[0.5; 8], 30.0) - 0.5).abs() < 1e-9);
+	}
+
+	#[test]
+	fn true_peak_finds_peaks_between_samples() {
+		// quarter sampling rate, sampled 45 degrees off its peaks
+		let samples = sine(256, 0.25, PI / 4.0);
+		let sample_peak = samples.iter().fold(0.0, |p: f64, x| p.max(x.abs()));
+		assert!((sample_peak - 0.5f64.sqrt()).abs() < 1e-9);
+		let peak = true_peak(&samples).unwrap();
+		assert!((peak - 1.0).abs() < 0.05, "{peak}");
+	}
+
+	#[test]
+	fn true_peak_of_nothing() {
+		assert_eq!(true_peak(&[]), None);
+		assert_eq!(true_peak(&[0.0; 4]), Some(0.0));
+	}
+}
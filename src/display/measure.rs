@@ -0,0 +1,183 @@
+use std::fmt::Display;
+
+/// what a measured value is, to format it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+	Plain,
+	Hertz,
+	Seconds,
+	Percent,
+	Decibel,
+}
+
+/// one measured quantity, with a value for each channel. channels where it
+/// makes no sense (like frequency of silence) have none
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+	pub name: &'static str,
+	pub unit: Unit,
+	pub values: Vec<Option<f64>>,
+}
+
+impl Measurement {
+	pub fn new(name: &'static str, unit: Unit, values: Vec<Option<f64>>) -> Self {
+		Measurement { name, unit, values }
+	}
+
+	/// value of given channel, formatted with its unit, or "-"
+	pub fn format(&self, channel: usize) -> String {
+		match self.values.get(channel).copied().flatten() {
+			Some(x) => Value(x, self.unit).to_string(),
+			None => "-".into(),
+		}
+	}
+}
+
+struct Value(f64, Unit);
+
+impl Display for Value {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let Value(x, unit) = *self;
+		match unit {
+			Unit::Plain => write!(f, "{:.3}", x),
+			Unit::Hertz if x.abs() >= 1000.0 => write!(f, "{:.2}kHz", x / 1000.0),
+			Unit::Hertz => write!(f, "{:.1}Hz", x),
+			Unit::Seconds if x.abs() >= 1.0 => write!(f, "{:.3}s", x),
+			Unit::Seconds if x.abs() >= 0.001 => write!(f, "{:.2}ms", x * 1e3),
+			Unit::Seconds => write!(f, "{:.0}µs", x * 1e6),
+			Unit::Percent => write!(f, "{:.1}%", x),
+			Unit::Decibel => write!(f, "{:.1}dB", x),
+		}
+	}
+}
+
+/// time in seconds, formatted like measurements
+pub fn seconds(x: f64) -> String {
+	Value(x, Unit::Seconds).to_string()
+}
+
+/// running average of measurements over the last frames. averaging over 1 frame shows them as they are
+#[derive(Debug, Clone)]
+pub struct Averager {
+	pub frames: u32,
+	last: Vec<Measurement>,
+}
+
+impl Default for Averager {
+	fn default() -> Self {
+		Averager { frames: 1, last: Vec::new() }
+	}
+}
+
+impl Averager {
+	/// fold a new frame of measurements in, starting over if they measure different things
+	pub fn update(&mut self, current: Vec<Measurement>) -> &[Measurement] {
+		let same = self.last.len() == current.len()
+			&& self.last.iter().zip(&current).all(|(a, b)| a.name == b.name && a.values.len() == b.values.len());
+		if !same || self.frames <= 1 {
+			self.last = current;
+			return &self.last;
+		}
+		let weight = 1.0 / self.frames as f64; // exponential, close enough and needs no history
+		for (avg, new) in self.last.iter_mut().zip(current) {
+			for (a, b) in avg.values.iter_mut().zip(new.values) {
+				*a = match (*a, b) {
+					(Some(a), Some(b)) => Some(a + (b - a) * weight),
+					(_, b) => b,
+				};
+			}
+		}
+		&self.last
+	}
+
+	pub fn clear(&mut self) {
+		self.last.clear();
+	}
+}
+
+/// time domain measurements of each channel, times based on given sampling rate
+pub fn waveform(data: &[&[f64]], sampling_rate: u32) -> Vec<Measurement> {
+	let stats : Vec<Option<Stats>> = data.iter().map(|x| Stats::of(x, sampling_rate as f64)).collect();
+	let get = |f: fn(&Stats) -> Option<f64>| stats.iter().map(|x| x.as_ref().and_then(f)).collect();
+	vec![
+		Measurement::new("freq", Unit::Hertz, get(|x| x.period.map(|p| 1.0 / p))),
+		Measurement::new("period", Unit::Seconds, get(|x| x.period)),
+		Measurement::new("p-p", Unit::Plain, get(|x| Some(x.max - x.min))),
+		Measurement::new("min", Unit::Plain, get(|x| Some(x.min))),
+		Measurement::new("max", Unit::Plain, get(|x| Some(x.max))),
+		Measurement::new("rms", Unit::Plain, get(|x| Some(x.rms))),
+		Measurement::new("mean", Unit::Plain, get(|x| Some(x.mean))),
+		Measurement::new("duty", Unit::Percent, get(|x| x.duty)),
+		Measurement::new("rise", Unit::Seconds, get(|x| x.rise)),
+		Measurement::new("fall", Unit::Seconds, get(|x| x.fall)),
+		Measurement::new("crest", Unit::Plain, get(|x| if x.rms > 0.0 { Some(x.min.abs().max(x.max.abs()) / x.rms) } else { None })),
+	]
+}
+
+struct Stats {
+	min: f64,
+	max: f64,
+	mean: f64,
+	rms: f64,
+	/// in seconds, like rise and fall times
+	period: Option<f64>,
+	/// share of time above mean, over whole periods, in percent
+	duty: Option<f64>,
+	/// average time to go from 10% to 90% of range, and back
+	rise: Option<f64>,
+	fall: Option<f64>,
+}
+
+impl Stats {
+	fn of(samples: &[f64], rate: f64) -> Option<Stats> {
+		if samples.is_empty() { return None }
+		let n = samples.len() as f64;
+		let min = samples.iter().copied().fold(f64::INFINITY, f64::min);
+		let max = samples.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+		let mean = samples.iter().sum::<f64>() / n;
+		let rms = (samples.iter().map(|x| x * x).sum::<f64>() / n).sqrt();
+
+		let mut stats = Stats { min, max, mean, rms, period: None, duty: None, rise: None, fall: None };
+		let range = max - min;
+		if range <= f64::EPSILON { return Some(stats) } // flat, nothing periodic
+
+		// rising crossings of the mean, with some hysteresis against noise
+		let hysteresis = range * 0.1;
+		let mut crossings = Vec::new();
+		let mut armed = false;
+		for (i, pair) in samples.windows(2).enumerate() {
+			if pair[1] < mean - hysteresis { armed = true }
+			if armed && pair[0] < mean && pair[1] >= mean {
+				crossings.push(i as f64 + (mean - pair[0]) / (pair[1] - pair[0])); // interpolated
+				armed = false;
+			}
+		}
+		if let (Some(first), Some(last)) = (crossings.first(), crossings.last()) {
+			if crossings.len() >= 2 {
+				let period = (last - first) / (crossings.len() - 1) as f64;
+				stats.period = Some(period / rate);
+				let whole = &samples[first.ceil() as usize..last.ceil() as usize];
+				let high = whole.iter().filter(|x| **x >= mean).count();
+				stats.duty = Some(100.0 * high as f64 / whole.len().max(1) as f64);
+			}
+		}
+
+		// 10% to 90% transitions, from last sample out of one band to first in the other
+		let (lo, hi) = (min + range * 0.1, max - range * 0.1);
+		let (mut low_at, mut high_at) = (None, None);
+		let (mut rises, mut falls) = (Vec::new(), Vec::new());
+		for (i, x) in samples.iter().enumerate() {
+			if *x <= lo {
+				if let Some(h) = high_at.take() { falls.push((i - h) as f64) }
+				low_at = Some(i);
+			} else if *x >= hi {
+				if let Some(l) = low_at.take() { rises.push((i - l) as f64) }
+				high_at = Some(i);
+			}
+		}
+		let average = |x: Vec<f64>| if x.is_empty() { None } else { Some(x.iter().sum::<f64>() / x.len() as f64 / rate) };
+		stats.rise = average(rises);
+		stats.fall = average(falls);
+		Some(stats)
+	}
+}
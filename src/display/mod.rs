@@ -1,12 +1,48 @@
 pub mod oscilloscope;
 pub mod vectorscope;
 pub mod spectroscope;
+pub mod widget;
+pub mod theme;
+pub mod cursor;
+pub mod measure;
+pub mod trigger;
+pub mod persistence;
+pub mod interpolate;
+pub mod spectrum;
 
-use crossterm::event::Event;
-use ratatui::{widgets::{Dataset, Axis, GraphType}, style::{Style, Color}, symbols::Marker};
+use ratatui::{widgets::{Dataset, Axis, GraphType}, style::{Style, Color}, symbols::Marker, text::Span, layout::Rect};
 
-use crate::input::Matrix;
+use crate::{app::update_value_i, input::{Matrix, StreamOptions}, keymap::{Action, Control}};
 
+pub use widget::Scope;
+pub use theme::Theme;
+pub use cursor::{Cursors, Readout};
+pub use measure::Measurement;
+pub use persistence::Persistence;
+
+/// scope-style divisions of the view, along X and Y
+pub const DIVISIONS: [f64; 2] = [10.0, 8.0];
+
+/// next value of the 1-2-5 sequence (..., 0.1, 0.2, 0.5, 1, 2, 5, ...) above or below given one
+pub fn step_125(x: f64, up: bool) -> f64 {
+	if x <= 0.0 || !x.is_finite() { return 1.0 }
+	let decade = 10f64.powf(x.log10().floor());
+	let steps = [0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0].map(|s| s * decade);
+	let tolerance = x * 1e-9; // so values already in the sequence move by a whole step
+	if up {
+		steps.into_iter().find(|s| *s > x + tolerance).unwrap_or(x)
+	} else {
+		steps.into_iter().rev().find(|s| *s < x - tolerance).unwrap_or(x)
+	}
+}
+
+/// evenly spaced values from start to end of a range, one every other division
+pub fn ticks(range: [f64; 2], divisions: f64) -> Vec<f64> {
+	let n = (divisions / 2.0).round().max(1.0) as usize;
+	(0..=n).map(|i| range[0] + (range[1] - range[0]) * i as f64 / n as f64).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Dimension {
 	X, Y
 }
@@ -20,24 +56,58 @@ pub struct GraphConfig {
 	pub width: u32,
 	pub scatter: bool,
 	pub references: bool,
+	/// scope-style grid of divisions over the plot
+	pub graticule: bool,
 	pub show_ui: bool,
 	pub marker_type: Marker,
 	pub palette: Vec<Color>,
 	pub labels_color: Color,
 	pub axis_color: Color,
+	/// how much view is panned from display bounds, in data units, for X and Y
+	pub offset: [f64; 2],
+	/// dots across plot, which traces can be decimated to. 0 keeps every sample
+	pub columns: u32,
+}
+
+impl Default for GraphConfig {
+	fn default() -> Self {
+		let theme = Theme::default();
+		GraphConfig {
+			pause: false,
+			samples: 2048,
+			sampling_rate: 48000,
+			scale: 1.0,
+			width: 2048,
+			scatter: false,
+			references: true,
+			graticule: false,
+			show_ui: true,
+			marker_type: Marker::Braille,
+			palette: theme.palette,
+			labels_color: theme.labels,
+			axis_color: theme.axis,
+			offset: [0.0, 0.0],
+			columns: 0,
+		}
+	}
 }
 
 impl GraphConfig {
+	/// color for given trace, cycling through palette. white if palette is empty
 	pub fn palette(&self, index: usize) -> Color {
-		*self.palette.get(index % self.palette.len()).unwrap_or(&Color::White)
+		index.checked_rem(self.palette.len())
+			.and_then(|i| self.palette.get(i))
+			.copied()
+			.unwrap_or(Color::White)
 	}
 }
 
 #[allow(clippy::ptr_arg)] // TODO temporarily! it's a shitty solution
 pub trait DisplayMode {
 	// MUST define
-	fn from_args(args: &crate::cfg::SourceOptions) -> Self where Self : Sized;
-	fn axis(&self, cfg: &GraphConfig, dimension: Dimension) -> Axis; // TODO simplify this
+	fn from_options(opts: &StreamOptions) -> Self where Self : Sized;
+	fn bounds(&self, cfg: &GraphConfig, dimension: Dimension) -> [f64; 2];
+	fn axis_name(&self, dimension: Dimension) -> &'static str;
 	fn process(&mut self, cfg: &GraphConfig, data: &Matrix<f64>) -> Vec<DataSet>;
 	fn mode_str(&self) -> &'static str;
 
@@ -45,15 +115,129 @@ pub trait DisplayMode {
 	fn channel_name(&self, index: usize) -> String { format!("{}", index) }
 	fn header(&self, _cfg: &GraphConfig) -> String { "".into() }
 	fn references(&self, _cfg: &GraphConfig) -> Vec<DataSet> { vec![] }
-	fn handle(&mut self, _event: Event) {}
+	/// text written over the plot along with references, at given data coordinates
+	fn annotations(&self, _cfg: &GraphConfig) -> Vec<(f64, f64, String)> { vec![] }
+	/// apply an action bound to a key, with given magnitude. ignore actions of other modes
+	fn perform(&mut self, _action: Action, _magnitude: f64) {}
+
+	/// whether this mode takes given action for itself, instead of app handling it too
+	fn captures(&self, _action: Action) -> bool { false }
+	/// controls specific to this mode and their current values, shown in help
+	fn controls(&self) -> Vec<Control> { vec![] }
+
+	/// whether processed datasets are traces of samples, which can be drawn with persistence
+	fn persistent(&self) -> bool { false }
+
+	/// live measurements of each channel of a frame of data, shown in side panel
+	fn measure(&self, _cfg: &GraphConfig, _data: &Matrix<f64>) -> Vec<Measurement> { vec![] }
+
+	/// values read by measurement cursors, from last processed datasets.
+	/// default just shows data coordinates and their differences
+	fn readout(&self, _cfg: &GraphConfig, cursors: &Cursors, _datasets: &[DataSet]) -> Vec<Readout> {
+		let mut out = Vec::new();
+		for (i, x) in cursors.x.iter().enumerate() {
+			if let Some(x) = x { out.push(Readout::new(format!("x{}", i + 1), format!("{:.3}", x)).of(i)) }
+		}
+		if let Some(dx) = cursors.delta(Dimension::X) { out.push(Readout::new("Δx", format!("{:.3}", dx))) }
+		for (i, y) in cursors.y.iter().enumerate() {
+			if let Some(y) = y { out.push(Readout::new(format!("y{}", i + 1), format!("{:.3}", y)).of(i + 2)) }
+		}
+		if let Some(dy) = cursors.delta(Dimension::Y) { out.push(Readout::new("Δy", format!("{:.3}", dy))) }
+		out
+	}
+
+	/// tick labels along a dimension, spread evenly from start to end of view.
+	/// default is plain values, one every other division
+	fn labels(&self, cfg: &GraphConfig, dimension: Dimension) -> Vec<String> {
+		ticks(self.view(cfg, dimension), DIVISIONS[dimension as usize]).into_iter().map(|x| format!("{:.2}", x)).collect()
+	}
+
+	/// size of a division along a dimension, formatted in its units, if it has a meaningful one.
+	/// default is plain size for linear axes
+	fn division(&self, cfg: &GraphConfig, dimension: Dimension) -> Option<String> {
+		let [a, b] = self.view(cfg, dimension);
+		Some(format!("{:.3}", (b - a) / DIVISIONS[dimension as usize]))
+	}
+
+	/// widen or narrow X range by some steps, for keys and scroll wheel. default adds or removes 25 samples a step
+	fn range_x(&self, cfg: &mut GraphConfig, increase: bool, magnitude: f64) {
+		update_value_i(&mut cfg.samples, increase, 25, magnitude, 0..cfg.width * 2);
+	}
+
+	/// handle a click on given data coordinates
+	fn click(&mut self, _cfg: &GraphConfig, _x: f64, _y: f64) {}
+
+	/// change zoom so that given range of data coordinates fits the view along a dimension.
+	/// default works for bounds proportional to samples (X) and scale (Y)
+	fn zoom(&self, cfg: &mut GraphConfig, dimension: Dimension, range: [f64; 2]) {
+		let [a, b] = self.bounds(cfg, dimension);
+		let factor = (range[1] - range[0]) / (b - a);
+		match dimension {
+			Dimension::X => cfg.samples = ((cfg.samples as f64 * factor) as u32).clamp(1, cfg.width * 2),
+			Dimension::Y => cfg.scale = (cfg.scale * factor).clamp(0.0001, 10.0),
+		}
+		self.center(cfg, dimension, range);
+	}
+
+	// DON'T override
+	fn axis(&self, cfg: &GraphConfig, dimension: Dimension) -> Axis<'_> {
+		let mut a = Axis::default();
+		if cfg.show_ui { // TODO don't make it necessary to check show_ui inside here
+			a = a.title(Span::styled(self.axis_name(dimension), Style::default().fg(cfg.labels_color)));
+		}
+		if cfg.show_ui {
+			let labels = self.labels(cfg, dimension);
+			if !labels.is_empty() {
+				a = a.labels(labels.into_iter().map(|x| Span::styled(x, Style::default().fg(cfg.labels_color))).collect());
+			}
+		}
+		a.style(Style::default().fg(cfg.axis_color)).bounds(self.view(cfg, dimension))
+	}
+
+	/// dotted grid over the view, along divisions of both dimensions
+	fn graticule(&self, cfg: &GraphConfig) -> Vec<DataSet> {
+		let [x0, x1] = self.view(cfg, Dimension::X);
+		let [y0, y1] = self.view(cfg, Dimension::Y);
+		let dots = |n: f64| (0..=(n * 5.0) as usize).map(move |i| i as f64 / (n * 5.0)); // 5 dots per division
+		let mut points = Vec::new();
+		for x in ticks([x0, x1], DIVISIONS[0] * 2.0) {
+			points.extend(dots(DIVISIONS[1]).map(|t| (x, y0 + (y1 - y0) * t)));
+		}
+		for y in ticks([y0, y1], DIVISIONS[1] * 2.0) {
+			points.extend(dots(DIVISIONS[0]).map(|t| (x0 + (x1 - x0) * t, y)));
+		}
+		vec![DataSet::new(None, points, cfg.marker_type, GraphType::Scatter, cfg.axis_color)]
+	}
+
+	/// bounds actually shown, after panning
+	fn view(&self, cfg: &GraphConfig, dimension: Dimension) -> [f64; 2] {
+		let [a, b] = self.bounds(cfg, dimension);
+		let offset = cfg.offset[dimension as usize];
+		[a + offset, b + offset]
+	}
+
+	/// pan so that view is centered on given range
+	fn center(&self, cfg: &mut GraphConfig, dimension: Dimension, range: [f64; 2]) {
+		let [a, b] = self.bounds(cfg, dimension);
+		cfg.offset[dimension as usize] = (range[0] + range[1]) / 2.0 - (a + b) / 2.0;
+	}
+
+	/// data coordinates at the center of a terminal cell, inside given plot area
+	fn coords(&self, cfg: &GraphConfig, area: Rect, column: u16, row: u16) -> (f64, f64) {
+		let [x0, x1] = self.view(cfg, Dimension::X);
+		let [y0, y1] = self.view(cfg, Dimension::Y);
+		let x = (column.saturating_sub(area.x) as f64 + 0.5) / area.width.max(1) as f64;
+		let y = (row.saturating_sub(area.y) as f64 + 0.5) / area.height.max(1) as f64;
+		(x0 + x * (x1 - x0), y1 - y * (y1 - y0))
+	}
 }
 
 pub struct DataSet {
-	name: Option<String>,
-	data: Vec<(f64, f64)>,
-	marker_type: Marker,
-	graph_type: GraphType,
-	color: Color,
+	pub(crate) name: Option<String>,
+	pub(crate) data: Vec<(f64, f64)>,
+	pub(crate) marker_type: Marker,
+	pub(crate) graph_type: GraphType,
+	pub(crate) color: Color,
 }
 
 impl<'a> From::<&'a DataSet> for Dataset<'a> {
@@ -83,3 +267,35 @@ impl DataSet {
 	}
 }
 
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn close(a: f64, b: f64) -> bool { (a - b).abs() <= b.abs() * 1e-9 }
+
+	#[test]
+	fn step_125_walks_sequence_up_and_down() {
+		let sequence = [0.01, 0.02, 0.05, 0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0];
+		for pair in sequence.windows(2) {
+			assert!(close(step_125(pair[0], true), pair[1]), "{} up", pair[0]);
+			assert!(close(step_125(pair[1], false), pair[0]), "{} down", pair[1]);
+		}
+	}
+
+	#[test]
+	fn step_125_snaps_values_off_sequence() {
+		assert!(close(step_125(3.0, true), 5.0));
+		assert!(close(step_125(3.0, false), 2.0));
+		assert!(close(step_125(0.7, true), 1.0));
+		assert!(close(step_125(0.7, false), 0.5));
+	}
+
+	#[test]
+	fn step_125_resets_invalid_values() {
+		for x in [0.0, -2.0, f64::NAN, f64::INFINITY] {
+			assert_eq!(step_125(x, true), 1.0);
+			assert_eq!(step_125(x, false), 1.0);
+		}
+	}
+}
@@ -1,21 +1,471 @@
-use crossterm::event::{Event, KeyModifiers, KeyCode};
-use ratatui::{widgets::{Axis, GraphType}, style::Style, text::Span};
+use std::ops::Range;
 
-use crate::{app::{update_value_f, update_value_i}, input::Matrix};
+use ratatui::widgets::GraphType;
 
-use super::{DisplayMode, GraphConfig, DataSet, Dimension};
+use crate::{app::update_value_f, input::{Matrix, StreamOptions}, keymap::{Action, Control}};
 
-#[derive(Default)]
+use super::{DisplayMode, GraphConfig, DataSet, Dimension, Cursors, Readout, Measurement, measure::{self, Unit}, interpolate, ticks, DIVISIONS, step_125};
+use super::trigger::{Trigger, TriggerKind, TriggerParams};
+
+/// without triggers for this long, auto mode free-runs
+const AUTO_TIMEOUT : f64 = 0.1;
+
+/// points across view that sin(x)/x interpolation aims for, at least
+const MIN_COLUMNS : usize = 256;
+
+/// most points per sample that sin(x)/x interpolation adds
+const MAX_UPSAMPLING : usize = 32;
+
+/// range of channel gains: zero would flatten a channel for good, and negative ones are what invert is for
+const GAIN_RANGE : (f64, f64) = (0.01, 100.0);
+
+/// what to show when trigger condition is not met
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerMode {
+	/// hold last triggered frame for a while, then free-run
+	#[default]
+	Auto,
+	/// hold last triggered frame until next trigger
+	Normal,
+	/// capture first triggered frame and hold it until rearmed
+	Single,
+}
+
+impl TriggerMode {
+	fn next(self) -> Self {
+		match self {
+			TriggerMode::Auto => TriggerMode::Normal,
+			TriggerMode::Normal => TriggerMode::Single,
+			TriggerMode::Single => TriggerMode::Auto,
+		}
+	}
+}
+
+impl std::fmt::Display for TriggerMode {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			TriggerMode::Auto => write!(f, "auto"),
+			TriggerMode::Normal => write!(f, "normal"),
+			TriggerMode::Single => write!(f, "single"),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TriggerStatus {
+	/// looking for trigger condition
+	#[default]
+	Armed,
+	/// last frame met trigger condition
+	Triggered,
+	/// ignoring triggers until holdoff is over
+	Waiting,
+	/// auto mode gave up waiting, showing live data
+	Free,
+	/// single capture done, until rearmed
+	Stopped,
+}
+
+impl std::fmt::Display for TriggerStatus {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			TriggerStatus::Armed => write!(f, "armed"),
+			TriggerStatus::Triggered => write!(f, "triggered"),
+			TriggerStatus::Waiting => write!(f, "waiting"),
+			TriggerStatus::Free => write!(f, "free"),
+			TriggerStatus::Stopped => write!(f, "stopped"),
+		}
+	}
+}
+
+/// signal watched for trigger condition. in configuration files, a channel
+/// index, "L", "R", "mid" (sum of first two channels) or "side" (difference)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub enum TriggerSource {
+	Channel(usize),
+	Mid,
+	Side,
+}
+
+impl Default for TriggerSource {
+	fn default() -> Self {
+		TriggerSource::Channel(0)
+	}
+}
+
+impl TriggerSource {
+	/// cycle through given number of channels, then math channels if there are at least two
+	fn next(self, channels: usize) -> Self {
+		match self {
+			TriggerSource::Channel(n) if n + 1 < channels => TriggerSource::Channel(n + 1),
+			TriggerSource::Channel(_) if channels >= 2 => TriggerSource::Mid,
+			TriggerSource::Mid => TriggerSource::Side,
+			_ => TriggerSource::Channel(0),
+		}
+	}
+}
+
+impl TryFrom<String> for TriggerSource {
+	type Error = String;
+
+	fn try_from(txt: String) -> Result<Self, Self::Error> {
+		match txt.to_lowercase().as_str() {
+			"mid" => Ok(TriggerSource::Mid),
+			"side" => Ok(TriggerSource::Side),
+			"l" | "left" => Ok(TriggerSource::Channel(0)),
+			"r" | "right" => Ok(TriggerSource::Channel(1)),
+			n => n.parse().map(TriggerSource::Channel).map_err(|_| format!("invalid trigger source '{}'", txt)),
+		}
+	}
+}
+
+impl std::fmt::Display for TriggerSource {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			TriggerSource::Channel(0) => write!(f, "L"),
+			TriggerSource::Channel(1) => write!(f, "R"),
+			TriggerSource::Channel(n) => write!(f, "{}", n),
+			TriggerSource::Mid => write!(f, "mid"),
+			TriggerSource::Side => write!(f, "side"),
+		}
+	}
+}
+
+/// how a channel is drawn, on top of global scale
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Input {
+	/// vertical position, added after gain
+	pub offset: f64,
+	/// multiplies samples, like volts per division of each channel
+	#[serde(deserialize_with = "clamped_gain")]
+	pub gain: f64,
+	pub invert: bool,
+	/// remove mean of each frame, like AC coupling, so signals with DC on them stay centered
+	pub ac: bool,
+	pub visible: bool,
+}
+
+impl Default for Input {
+	fn default() -> Self {
+		Input { offset: 0.0, gain: 1.0, invert: false, ac: false, visible: true }
+	}
+}
+
+impl Input {
+	/// what coupling removes from given samples
+	fn bias(&self, samples: &[f64]) -> f64 {
+		match self.ac && !samples.is_empty() {
+			true => samples.iter().sum::<f64>() / samples.len() as f64,
+			false => 0.0,
+		}
+	}
+
+	fn factor(&self) -> f64 {
+		if self.invert { -self.gain } else { self.gain }
+	}
+
+	/// where given sample is drawn, with given bias removed
+	fn map(&self, x: f64, bias: f64) -> f64 {
+		(x - bias) * self.factor() + self.offset
+	}
+
+	/// sample drawn at given position, with given bias removed
+	fn unmap(&self, y: f64, bias: f64) -> f64 {
+		(y - self.offset) / self.factor() + bias
+	}
+}
+
+/// gain read from configuration, brought back in range
+fn clamped_gain<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+	let gain = <f64 as serde::Deserialize>::deserialize(deserializer)?;
+	Ok(gain.max(GAIN_RANGE.0).min(GAIN_RANGE.1)) // unlike clamp, also catches nan
+}
+
+/// settings that differ from defaults, as shown in legend
+impl std::fmt::Display for Input {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.gain != 1.0 { write!(f, " ×{:.2}", self.gain)? }
+		if self.offset != 0.0 { write!(f, " {:+.2}", self.offset)? }
+		if self.invert { write!(f, " inv")? }
+		if self.ac { write!(f, " AC")? }
+		Ok(())
+	}
+}
+
+/// capture shown, with index of its trigger
+#[derive(Debug, Clone)]
+enum Held {
+	/// trigger in current capture
+	Capture(usize),
+	/// capture that moved on since, without triggering again
+	Copy(Matrix<f64>, usize),
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Oscilloscope {
 	pub triggering: bool,
+	pub kind: TriggerKind,
 	pub falling_edge: bool,
+	/// level to cross, from -1 to 1
 	pub threshold: f64,
-	pub depth: u32,
+	/// how far beyond threshold the signal must go, on the other side, before triggering again
+	pub hysteresis: f64,
+	/// half height of the band around threshold, for window, runt and slope triggers
+	pub band: f64,
+	/// seconds a pulse or slope must last, for pulse and slope triggers
+	pub width: f64,
+	/// trigger on pulses and slopes longer than width, instead of shorter
+	pub longer: bool,
+	pub source: TriggerSource,
+	/// where trigger point is on screen, from 0 (left edge) to 1 (right edge)
+	pub position: f64,
 	pub peaks: bool,
+	/// reconstruct band-limited signal between samples when zoomed in, instead of straight lines
+	pub sinc: bool,
+	/// drawing settings of each channel, by index. missing ones use defaults
+	pub inputs: Vec<Input>,
+	/// channel whose settings arrows change, instead of ranges
+	#[serde(skip)]
+	selected: Option<usize>,
+	/// what coupling removed from trigger source in last frame, to place trigger level on it
+	#[serde(skip)]
+	bias: f64,
+	pub mode: TriggerMode,
+	/// seconds after a trigger during which new ones are ignored
+	pub holdoff: f64,
+	#[serde(skip)]
+	pub status: TriggerStatus,
+	/// triggers since enabled or rearmed
+	#[serde(skip)]
+	pub count: u64,
+	/// last capture shown, with index of its trigger, so it can be panned through
+	#[serde(skip)]
+	held: Option<Held>,
+	/// samples seen since last trigger, across frames
+	#[serde(skip)]
+	since: usize,
+	/// last frames joined together, to show what happened before trigger
+	#[serde(skip)]
+	capture: Matrix<f64>,
+	/// capture index where scanning for triggers resumes
+	#[serde(skip)]
+	scanned: usize,
+	/// capture index before which holdoff ignores triggers
+	#[serde(skip)]
+	holdoff_until: usize,
+	/// watches trigger signal, built on first scan so it starts from a clean state
+	#[serde(skip)]
+	detector: Option<Box<dyn Trigger>>,
+	/// capture indexes where trigger condition was met, but not shown yet
+	#[serde(skip)]
+	pending: Vec<usize>,
+	#[serde(skip)]
+	channels: usize,
+}
+
+impl Default for Oscilloscope {
+	fn default() -> Self {
+		Oscilloscope {
+			triggering: false,
+			kind: TriggerKind::default(),
+			falling_edge: false,
+			threshold: 0.0,
+			hysteresis: 0.0,
+			band: 0.1,
+			width: 0.001,
+			longer: false,
+			source: TriggerSource::default(),
+			position: 0.0,
+			peaks: false,
+			sinc: false,
+			inputs: Vec::new(),
+			selected: None,
+			bias: 0.0,
+			mode: TriggerMode::default(),
+			holdoff: 0.0,
+			status: TriggerStatus::default(),
+			count: 0,
+			held: None,
+			since: 0,
+			capture: Matrix::default(),
+			scanned: 0,
+			holdoff_until: 0,
+			detector: None,
+			pending: Vec::new(),
+			channels: 0,
+		}
+	}
+}
+
+impl Oscilloscope {
+	/// add latest frame to capture and look for triggers in it, updating what is shown, status and counter
+	fn trigger(&mut self, cfg: &GraphConfig, data: &Matrix<f64>) {
+		let Some(len) = data.first().map(|x| x.len()) else { return };
+		if cfg.pause || self.status == TriggerStatus::Stopped { // same data again, don't count it twice
+			return;
+		}
+
+		// keep enough to fill the screen around a trigger found anywhere in latest frame
+		self.channels = data.len();
+		if self.capture.len() != data.len() {
+			if let Some(Held::Capture(at)) = self.held {
+				self.held = Some(Held::Copy(std::mem::take(&mut self.capture), at));
+			}
+			self.capture = vec![Vec::new(); data.len()];
+			self.scanned = 0;
+		}
+		let samples = cfg.samples as usize;
+		let excess = self.capture[0].len().saturating_sub(samples);
+		match self.held {
+			// shown capture is about to scroll away: move it out whole, in case nothing triggers again
+			Some(Held::Capture(at)) => {
+				let previous = std::mem::take(&mut self.capture);
+				self.capture = previous.iter().zip(data).map(|(old, new)| [&old[excess..], new.as_slice()].concat()).collect();
+				self.held = Some(Held::Copy(previous, at));
+			},
+			_ => for (capture, chan) in self.capture.iter_mut().zip(data) {
+				capture.drain(..excess);
+				capture.extend_from_slice(chan);
+			},
+		}
+		self.scanned = self.scanned.saturating_sub(excess);
+		self.holdoff_until = self.holdoff_until.saturating_sub(excess);
+		self.pending.retain_mut(|i| match i.checked_sub(excess) {
+			Some(shifted) => { *i = shifted; true },
+			None => false,
+		});
+
+		let signal = self.signal();
+		let pre = self.pre(cfg) as usize;
+		let post = samples.saturating_sub(pre);
+		match self.scan(cfg, &signal, pre, post) {
+			Some(at) => {
+				self.count += 1;
+				self.since = signal.len() - at;
+				self.holdoff_until = at + (self.holdoff * cfg.sampling_rate as f64) as usize;
+				self.status = if self.mode == TriggerMode::Single { TriggerStatus::Stopped } else { TriggerStatus::Triggered };
+				self.held = Some(Held::Capture(at));
+			},
+			None => {
+				self.since = self.since.saturating_add(len);
+				self.status = if self.holdoff_until >= signal.len() { TriggerStatus::Waiting } else { TriggerStatus::Armed };
+				if self.mode == TriggerMode::Auto && self.since as f64 >= AUTO_TIMEOUT * cfg.sampling_rate as f64 {
+					self.status = TriggerStatus::Free;
+					let start = signal.len().saturating_sub(samples);
+					self.held = Some(Held::Capture(start + pre));
+				}
+			},
+		}
+	}
+
+	/// capture shown and index of its trigger, or None if nothing triggered yet
+	fn shown(&self) -> Option<(&Matrix<f64>, usize)> {
+		match &self.held {
+			Some(Held::Capture(at)) => Some((&self.capture, *at)),
+			Some(Held::Copy(capture, at)) => Some((capture, *at)),
+			None => None,
+		}
+	}
+
+	/// data plotted and index of sample at time zero: shown capture when triggering, latest frame otherwise
+	fn frame<'a>(&'a self, cfg: &GraphConfig, data: &'a Matrix<f64>) -> Option<(&'a Matrix<f64>, usize)> {
+		match self.triggering {
+			true => self.shown(),
+			false => Some((data, self.pre(cfg) as usize)),
+		}
+	}
+
+	/// first trigger in captured signal with enough samples around it to fill the screen.
+	/// new samples go through the detector once, triggers too recent to show wait for next frame
+	fn scan(&mut self, cfg: &GraphConfig, signal: &[f64], pre: usize, post: usize) -> Option<usize> {
+		let params = TriggerParams {
+			threshold: self.threshold,
+			hysteresis: self.hysteresis,
+			band: self.band,
+			width: self.width * cfg.sampling_rate as f64,
+			longer: self.longer,
+			falling: self.falling_edge,
+		};
+		let detector = self.detector.get_or_insert_with(|| self.kind.build());
+		for (i, x) in signal.iter().enumerate().skip(self.scanned) {
+			if detector.step(*x, &params) {
+				self.pending.push(i);
+			}
+		}
+		self.scanned = signal.len();
+
+		let holdoff_until = self.holdoff_until;
+		self.pending.retain(|i| *i >= pre && *i >= holdoff_until);
+		let at = self.pending.first().copied().filter(|i| i + post <= signal.len())?;
+		self.pending.retain(|i| *i > at); // at most one trigger per frame, later ones wait for next
+		Some(at)
+	}
+
+	/// captured signal watched for triggers, channel 0 if selected one is missing
+	fn signal(&self) -> Vec<f64> {
+		let capture = &self.capture;
+		match (self.source, capture.as_slice()) {
+			(TriggerSource::Channel(n), _) if n < capture.len() => capture[n].clone(),
+			(TriggerSource::Mid, [l, r, ..]) => l.iter().zip(r).map(|(l, r)| (l + r) / 2.0).collect(),
+			(TriggerSource::Side, [l, r, ..]) => l.iter().zip(r).map(|(l, r)| (l - r) / 2.0).collect(),
+			_ => capture.first().cloned().unwrap_or_default(),
+		}
+	}
+
+	/// samples shown before trigger point
+	fn pre(&self, cfg: &GraphConfig) -> f64 {
+		(self.position.clamp(0.0, 1.0) * cfg.samples as f64).round()
+	}
+
+	/// horizontal scale, in seconds per division
+	fn timebase(&self, cfg: &GraphConfig) -> f64 {
+		cfg.samples as f64 / cfg.sampling_rate as f64 / DIVISIONS[0]
+	}
+
+	/// settings of given channel, defaults if not configured
+	fn input(&self, channel: usize) -> Input {
+		self.inputs.get(channel).copied().unwrap_or_default()
+	}
+
+	fn input_mut(&mut self, channel: usize) -> &mut Input {
+		if self.inputs.len() <= channel {
+			self.inputs.resize(channel + 1, Input::default());
+		}
+		&mut self.inputs[channel]
+	}
+
+	/// input of trigger source, if it's a single channel
+	fn source_input(&self) -> Option<Input> {
+		match self.source {
+			TriggerSource::Channel(n) => Some(self.input(n)),
+			_ => None,
+		}
+	}
+
+	/// channel name followed by its settings, marked if selected
+	fn legend(&self, channel: usize) -> String {
+		let mark = if self.selected == Some(channel) { "▸" } else { "" };
+		format!("{}{}{}", mark, self.channel_name(channel), self.input(channel))
+	}
+
+	/// start looking for triggers again, forgetting held frame and counter
+	fn rearm(&mut self) {
+		self.status = TriggerStatus::Armed;
+		self.held = None;
+		self.count = 0;
+		self.since = usize::MAX; // no holdoff for first trigger
+		self.holdoff_until = 0;
+		self.detector = None;
+		self.pending.clear();
+	}
 }
 
 impl DisplayMode for Oscilloscope {
-	fn from_args(_opts: &crate::cfg::SourceOptions) -> Self {
+	fn from_options(_opts: &StreamOptions) -> Self {
 		Oscilloscope::default()
 	}
 
@@ -34,67 +484,87 @@ impl DisplayMode for Oscilloscope {
 	fn header(&self, _: &GraphConfig) -> String {
 		if self.triggering {
 			format!(
-				"{} {:.0}{} trigger",
+				"{}{}{:.2}{} {}/{} {}",
+				if self.kind == TriggerKind::Edge { "".into() } else { format!("{} ", self.kind) },
 				if self.falling_edge { "v" } else { "^" },
 				self.threshold,
-				if self.depth > 1 { format!(":{}", self.depth) } else { "".into() },
+				if self.source == TriggerSource::Channel(0) { "".into() } else { format!(":{}", self.source) },
+				self.mode,
+				self.status,
+				self.count,
 			)
 		} else {
 			"live".into()
 		}
 	}
 
-	fn axis(&self, cfg: &GraphConfig, dimension: Dimension) -> Axis {
-		let (name, bounds) = match dimension {
-			Dimension::X => ("time -", [0.0, cfg.samples as f64]),
-			Dimension::Y => ("| amplitude", [-cfg.scale, cfg.scale]),
-		};
-		let mut a = Axis::default();
-		if cfg.show_ui { // TODO don't make it necessary to check show_ui inside here
-			a = a.title(Span::styled(name, Style::default().fg(cfg.labels_color)));
+	fn axis_name(&self, dimension: Dimension) -> &'static str {
+		match dimension {
+			Dimension::X => "time -",
+			Dimension::Y => "| amplitude",
+		}
+	}
+
+	fn bounds(&self, cfg: &GraphConfig, dimension: Dimension) -> [f64; 2] {
+		match dimension {
+			Dimension::X => { // seconds from trigger
+				let pre = self.pre(cfg);
+				let rate = cfg.sampling_rate as f64;
+				[-pre / rate, (cfg.samples as f64 - pre) / rate]
+			},
+			Dimension::Y => [-cfg.scale, cfg.scale],
 		}
-		a.style(Style::default().fg(cfg.axis_color)).bounds(bounds)
 	}
 
 	fn references(&self, cfg: &GraphConfig) -> Vec<DataSet> {
 		vec![
-			DataSet::new(None, vec![(0.0, 0.0), (cfg.samples as f64, 0.0)], cfg.marker_type, GraphType::Line, cfg.axis_color),
+			DataSet::new(None, self.view(cfg, Dimension::X).map(|x| (x, 0.0)).to_vec(), cfg.marker_type, GraphType::Line, cfg.axis_color),
 		]
 	}
 
 	fn process(&mut self, cfg: &GraphConfig, data: &Matrix<f64>) -> Vec<DataSet> {
 		let mut out = Vec::new();
 
-		let mut trigger_offset = 0;
-		if self.depth == 0 { self.depth = 1 }
 		if self.triggering {
-			for i in 0..data[0].len() {
-				if triggered(&data[0], i, self.threshold, self.depth, self.falling_edge) { // triggered
-					break;
-				}
-				trigger_offset += 1;
-			}
+			self.trigger(cfg, data);
 		}
+		let Some((data, origin)) = self.frame(cfg, data) else {
+			return out; // nothing triggered yet
+		};
+		let rate = cfg.sampling_rate as f64;
+		let start = -(origin as f64) / rate;
 
+		let channels = data.len();
+		let bias = match self.source {
+			TriggerSource::Channel(n) => data.get(n).map(|x| self.input(n).bias(x)).unwrap_or(0.0),
+			_ => 0.0,
+		};
 		if self.triggering {
-			out.push(DataSet::new(Some("T".into()), vec![(0.0, self.threshold)], cfg.marker_type, GraphType::Scatter, cfg.labels_color));
+			let level = self.source_input().map(|x| x.map(self.threshold, bias)).unwrap_or(self.threshold);
+			out.push(DataSet::new(Some("T".into()), vec![(0.0, level)], cfg.marker_type, GraphType::Scatter, cfg.labels_color));
 		}
 
+		let view = self.view(cfg, Dimension::X);
 		for (n, channel) in data.iter().enumerate().rev() {
+			let input = self.input(n);
+			if !input.visible { continue }
+			let bias = input.bias(channel);
+			let channel = &channel.iter().map(|x| input.map(*x, bias)).collect::<Vec<f64>>();
 			let (mut min, mut max) = (0.0, 0.0);
-			let mut tmp = Vec::new();
-			for (i, sample) in channel.iter().enumerate() {
+			for sample in channel[visible(channel.len(), start, rate, view)].iter() {
 				if *sample < min { min = *sample };
 				if *sample > max { max = *sample };
-				if i >= trigger_offset {
-					tmp.push(((i - trigger_offset) as f64, *sample));
-				}
 			}
+			let tmp = match (self.sinc, cfg.scatter) {
+				(true, _) => reconstruct(channel, start, rate, view, cfg.columns as usize),
+				(false, true) => decimate(channel, start, rate, view, 0), // every dot counts
+				(false, false) => decimate(channel, start, rate, view, cfg.columns as usize),
+			};
 
 			if self.peaks {
 				out.push(DataSet::new(
 					None,
-					vec![(0.0, min), (0.0, max)],
+					vec![(view[0], min), (view[0], max)],
 					cfg.marker_type,
 					GraphType::Scatter,
 					cfg.palette(n)
@@ -102,7 +572,7 @@ impl DisplayMode for Oscilloscope {
 			}
 
 			out.push(DataSet::new(
-				Some(self.channel_name(n)),
+				Some(self.legend(n)),
 				tmp,
 				cfg.marker_type,
 				if cfg.scatter { GraphType::Scatter } else { GraphType::Line },
@@ -110,60 +580,287 @@ impl DisplayMode for Oscilloscope {
 			));
 		}
 
+		self.channels = channels;
+		self.bias = bias;
 		out
 	}
 
-	fn handle(&mut self, event: Event) {
-		if let Event::Key(key) = event {
-			let magnitude = match key.modifiers {
-				KeyModifiers::SHIFT => 10.0,
-				KeyModifiers::CONTROL => 5.0,
-				KeyModifiers::ALT => 0.2,
-				_ => 1.0,
-			};
-			match key.code {
-				KeyCode::PageUp   => update_value_f(&mut self.threshold, 250.0, magnitude, 0.0..32768.0),
-				KeyCode::PageDown => update_value_f(&mut self.threshold, -250.0, magnitude, 0.0..32768.0),
-				KeyCode::Char('t') => self.triggering   = !self.triggering,
-				KeyCode::Char('e') => self.falling_edge = !self.falling_edge,
-				KeyCode::Char('p') => self.peaks        = !self.peaks,
-				KeyCode::Char('=') => update_value_i(&mut self.depth, true, 1, 1.0, 1..65535),
-				KeyCode::Char('-') => update_value_i(&mut self.depth, false, 1, 1.0, 1..65535),
-				KeyCode::Char('+') => update_value_i(&mut self.depth, true, 10, 1.0, 1..65535),
-				KeyCode::Char('_') => update_value_i(&mut self.depth, false, 10, 1.0, 1..65535),
-				KeyCode::Esc => {
-					self.triggering = false;
-				},
-				_ => {}
+	fn persistent(&self) -> bool { true }
+
+	/// of samples in view, same as plotted
+	fn measure(&self, cfg: &GraphConfig, data: &Matrix<f64>) -> Vec<Measurement> {
+		let rate = cfg.sampling_rate as f64;
+		let view = self.view(cfg, Dimension::X);
+		let window : Vec<&[f64]> = match self.frame(cfg, data) {
+			Some((frame, origin)) => frame.iter().map(|x| &x[visible(x.len(), -(origin as f64) / rate, rate, view)]).collect(),
+			None => Vec::new(), // nothing triggered yet
+		};
+		let mut out = measure::waveform(&window, cfg.sampling_rate);
+		if self.sinc {
+			out.push(Measurement::new("true pk", Unit::Plain, window.iter().map(|x| interpolate::true_peak(x)).collect()));
+		}
+		out
+	}
+
+	fn readout(&self, cfg: &GraphConfig, cursors: &Cursors, _datasets: &[DataSet]) -> Vec<Readout> {
+		let ms = |seconds: f64| format!("{:.3}ms", seconds * 1000.0);
+		let mut out = Vec::new();
+		for (i, x) in cursors.x.iter().enumerate() {
+			if let Some(x) = x { out.push(Readout::new(format!("t{}", i + 1), ms(*x)).of(i)) }
+		}
+		if let Some(dt) = cursors.delta(Dimension::X) {
+			out.push(Readout::new("Δt", ms(dt)));
+			out.push(Readout::new("", format!("{:.0} samples", dt * cfg.sampling_rate as f64)));
+			if dt != 0.0 {
+				out.push(Readout::new("1/Δt", format!("{:.2}Hz", 1.0 / dt.abs())));
 			}
 		}
+		for (i, y) in cursors.y.iter().enumerate() {
+			if let Some(y) = y { out.push(Readout::new(format!("v{}", i + 1), format!("{:.3}", y)).of(i + 2)) }
+		}
+		if let Some(dv) = cursors.delta(Dimension::Y) {
+			out.push(Readout::new("ΔV", format!("{:.3}", dv)));
+		}
+		out
+	}
+
+	fn labels(&self, cfg: &GraphConfig, dimension: Dimension) -> Vec<String> {
+		let ticks = ticks(self.view(cfg, dimension), DIVISIONS[dimension as usize]);
+		match dimension {
+			Dimension::X => ticks.into_iter().map(measure::seconds).collect(),
+			Dimension::Y => ticks.into_iter().map(|x| format!("{:.2}", x)).collect(),
+		}
+	}
+
+	fn division(&self, cfg: &GraphConfig, dimension: Dimension) -> Option<String> {
+		let [a, b] = self.view(cfg, dimension);
+		match dimension {
+			Dimension::X => Some(measure::seconds(self.timebase(cfg))),
+			Dimension::Y => Some(format!("{:.3}", (b - a) / DIVISIONS[1])),
+		}
+	}
+
+	/// steps through 1-2-5 timebases, keeping trigger point where it is on screen
+	fn range_x(&self, cfg: &mut GraphConfig, increase: bool, magnitude: f64) {
+		let mut timebase = self.timebase(cfg);
+		for _ in 0..(magnitude.round() as usize).max(1) {
+			timebase = step_125(timebase, increase);
+		}
+		let samples = (timebase * DIVISIONS[0] * cfg.sampling_rate as f64).round().clamp(1.0, cfg.width as f64 * 2.0);
+		cfg.offset[0] *= samples / cfg.samples.max(1) as f64;
+		cfg.samples = samples as u32;
+	}
+
+	fn click(&mut self, _cfg: &GraphConfig, _x: f64, y: f64) {
+		self.threshold = self.source_input().map(|x| x.unmap(y, self.bias)).unwrap_or(y);
+	}
+
+	fn controls(&self) -> Vec<Control> {
+		vec![
+			Control::toggle(Action::Trigger, "triggering", self.triggering),
+			Control::new(&[Action::TriggerType], "trigger type", self.kind),
+			Control::new(&[Action::Edge], "trigger edge", if self.falling_edge { "falling" } else { "rising" }),
+			Control::new(&[Action::ThresholdUp, Action::ThresholdDown], "trigger threshold (or click)", format!("{:.2}", self.threshold)),
+			Control::new(&[Action::HysteresisUp, Action::HysteresisDown], "trigger hysteresis", format!("{:.2}", self.hysteresis)),
+			Control::new(&[Action::BandUp, Action::BandDown], "trigger band (window, runt, slope)", format!("±{:.2}", self.band)),
+			Control::new(&[Action::WidthUp, Action::WidthDown], "trigger width (pulse, slope)", measure::seconds(self.width)),
+			Control::new(&[Action::Longer], "trigger on widths", if self.longer { "longer" } else { "shorter" }),
+			Control::new(&[Action::TriggerSource], "trigger source", self.source),
+			Control::new(&[Action::TriggerLeft, Action::TriggerRight], "trigger position", format!("{:.0}%", self.position * 100.0)),
+			Control::new(&[Action::TriggerMode], "trigger mode", self.mode),
+			Control::new(&[Action::HoldoffUp, Action::HoldoffDown], "trigger holdoff", format!("{:.0}ms", self.holdoff * 1000.0)),
+			Control::new(&[Action::Arm], "rearm trigger", format!("{}, {} triggers", self.status, self.count)),
+			Control::toggle(Action::Peaks, "peaks", self.peaks),
+			Control::toggle(Action::Sinc, "sin(x)/x interpolation", self.sinc),
+			Control::new(&[Action::SelectChannel], "channel for arrows (position, gain)", match self.selected {
+				Some(n) => self.legend(n),
+				None => "none".into(),
+			}),
+			Control::new(&[Action::Invert, Action::Coupling, Action::HideChannel], "invert, AC/DC, show/hide channel",
+				format!("{} hidden", (0..self.channels).filter(|n| !self.input(*n).visible).count())),
+		]
+	}
+
+	fn captures(&self, action: Action) -> bool {
+		self.selected.is_some() && matches!(action, Action::YRangeUp | Action::YRangeDown | Action::XRangeUp | Action::XRangeDown)
+	}
+
+	fn perform(&mut self, action: Action, magnitude: f64) {
+		match action {
+			Action::ThresholdUp   => update_value_f(&mut self.threshold, 0.01, magnitude, -1.0..1.0),
+			Action::ThresholdDown => update_value_f(&mut self.threshold, -0.01, magnitude, -1.0..1.0),
+			Action::HysteresisUp   => update_value_f(&mut self.hysteresis, 0.01, magnitude, 0.0..1.0),
+			Action::HysteresisDown => update_value_f(&mut self.hysteresis, -0.01, magnitude, 0.0..1.0),
+			Action::TriggerLeft   => update_value_f(&mut self.position, -0.05, magnitude, 0.0..1.0),
+			Action::TriggerRight  => update_value_f(&mut self.position, 0.05, magnitude, 0.0..1.0),
+			Action::BandUp        => update_value_f(&mut self.band, 0.01, magnitude, 0.0..1.0),
+			Action::BandDown      => update_value_f(&mut self.band, -0.01, magnitude, 0.0..1.0),
+			Action::WidthUp       => update_value_f(&mut self.width, 0.00001, magnitude, 0.0..1.0),
+			Action::WidthDown     => update_value_f(&mut self.width, -0.00001, magnitude, 0.0..1.0),
+			Action::Longer        => self.longer = !self.longer,
+			Action::TriggerType   => {
+				self.kind = self.kind.next();
+				self.rearm();
+			},
+			Action::TriggerSource => {
+				self.source = self.source.next(self.channels);
+				self.rearm();
+			},
+			Action::Trigger       => {
+				self.triggering = !self.triggering;
+				self.rearm();
+			},
+			Action::TriggerMode   => {
+				self.mode = self.mode.next();
+				self.rearm();
+			},
+			Action::Arm           => self.rearm(),
+			Action::HoldoffUp     => update_value_f(&mut self.holdoff, 0.001, magnitude, 0.0..1.0),
+			Action::HoldoffDown   => update_value_f(&mut self.holdoff, -0.001, magnitude, 0.0..1.0),
+			Action::Edge          => self.falling_edge = !self.falling_edge,
+			Action::Peaks         => self.peaks        = !self.peaks,
+			Action::Sinc          => self.sinc         = !self.sinc,
+			Action::SelectChannel => self.selected = match self.selected {
+				None if self.channels > 0 => Some(0),
+				Some(n) if n + 1 < self.channels => Some(n + 1),
+				_ => None,
+			},
+			Action::YRangeUp | Action::YRangeDown => if let Some(n) = self.selected {
+				let step = if action == Action::YRangeUp { 0.01 } else { -0.01 };
+				update_value_f(&mut self.input_mut(n).offset, step, magnitude, -10.0..10.0);
+			},
+			Action::XRangeUp | Action::XRangeDown => if let Some(n) = self.selected {
+				let input = self.input_mut(n);
+				input.gain = step_125(input.gain, action == Action::XRangeUp).clamp(GAIN_RANGE.0, GAIN_RANGE.1);
+			},
+			Action::Invert => if let Some(n) = self.selected {
+				let input = self.input_mut(n);
+				input.invert = !input.invert;
+			},
+			Action::Coupling => if let Some(n) = self.selected {
+				let input = self.input_mut(n);
+				input.ac = !input.ac;
+			},
+			Action::HideChannel => if let Some(n) = self.selected {
+				let input = self.input_mut(n);
+				input.visible = !input.visible;
+			},
+			Action::Reset => {
+				self.triggering = false;
+				self.selected = None;
+			},
+			_ => {}
+		}
 	}
 }
 
-#[allow(clippy::collapsible_else_if)] // TODO can this be made nicer?
-fn triggered(data: &[f64], index: usize, threshold: f64, depth: u32, falling_edge:bool) -> bool {
-	if data.len() < index + (1+depth as usize) { return false; }
-	if falling_edge {
-		if data[index] >= threshold {
-			for i in 1..=depth as usize {
-				if data[index+i] >= threshold {
-					return false;
-				}
-			}
-			true
-		} else {
-			false
+/// points of samples starting at given time, reduced to the min and max of each column across
+/// view, in the order they came, so that lines still reach every peak however many samples fall
+/// in a column. samples out of view are dropped, except those next to it which lines go to.
+/// every sample is kept with no columns, or when there are already few enough
+fn decimate(channel: &[f64], start: f64, rate: f64, view: [f64; 2], columns: usize) -> Vec<(f64, f64)> {
+	let point = |i: usize| (start + i as f64 / rate, channel[i]);
+	let Range { start: first, end: last } = visible(channel.len(), start, rate, view);
+	if columns == 0 || last - first <= columns * 2 {
+		return (0..channel.len()).map(point).collect();
+	}
+	let per = (last - first) as f64 / columns as f64;
+	let mut out = Vec::with_capacity(columns * 2 + 2);
+	if first > 0 { out.push(point(first - 1)) }
+	for column in 0..columns {
+		let a = first + (column as f64 * per) as usize;
+		let b = (first + ((column + 1) as f64 * per) as usize).min(last);
+		if a >= b { continue }
+		let (mut low, mut high) = (a, a);
+		for i in a..b {
+			if channel[i] < channel[low] { low = i }
+			if channel[i] > channel[high] { high = i }
 		}
-	} else {
-		if data[index] <= threshold {
-			for i in 1..=depth as usize {
-				if data[index+i] <= threshold {
-					return false;
-				}
-			}
-			true
-		} else {
-			false
+		out.push(point(low.min(high)));
+		if low != high { out.push(point(low.max(high))) }
+	}
+	if last < channel.len() { out.push(point(last)) }
+	out
+}
+
+/// points of samples starting at given time, upsampled with sin(x)/x across view until there are
+/// about as many as columns, plus samples next to it which lines go to. zoomed out far enough
+/// that nothing is between them, samples are decimated instead
+fn reconstruct(channel: &[f64], start: f64, rate: f64, view: [f64; 2], columns: usize) -> Vec<(f64, f64)> {
+	let range = visible(channel.len(), start, rate, view);
+	let factor = (columns.max(MIN_COLUMNS) / range.len().max(1)).min(MAX_UPSAMPLING);
+	if factor <= 1 { return decimate(channel, start, rate, view, columns) }
+	let range = range.start.saturating_sub(1)..(range.end + 1).min(channel.len());
+	interpolate::upsample(channel, range, factor)
+		.into_iter()
+		.map(|(at, x)| (start + at / rate, x))
+		.collect()
+}
+
+/// indexes of samples starting at given time that fall in view, plus the one right after
+fn visible(len: usize, start: f64, rate: f64, view: [f64; 2]) -> Range<usize> {
+	let first = (((view[0] - start) * rate).floor().max(0.0) as usize).min(len);
+	let last = (((view[1] - start) * rate).ceil().max(0.0) as usize + 1).min(len);
+	first..last.max(first)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn configured_gain_stays_in_range() {
+		let gain = |txt: &str| toml::from_str::<Input>(txt).unwrap().gain;
+		assert_eq!(gain("gain = 2.0"), 2.0);
+		assert_eq!(gain("gain = 0.0"), GAIN_RANGE.0);
+		assert_eq!(gain("gain = -5.0"), GAIN_RANGE.0);
+		assert_eq!(gain("gain = 1e9"), GAIN_RANGE.1);
+		assert_eq!(gain("gain = nan"), GAIN_RANGE.0);
+		assert_eq!(gain(""), 1.0);
+	}
+
+	#[test]
+	fn unmap_reverses_map() {
+		let input = Input { offset: 0.3, gain: 2.0, invert: true, ..Input::default() };
+		assert!((input.unmap(input.map(0.25, 0.1), 0.1) - 0.25).abs() < 1e-12);
+	}
+
+	#[test]
+	fn visible_covers_view_and_sample_after() {
+		assert_eq!(visible(100, 0.0, 10.0, [0.0, 10.0]), 0..100);
+		assert_eq!(visible(100, 0.0, 10.0, [2.0, 3.0]), 20..31);
+		assert_eq!(visible(100, 1.0, 10.0, [2.0, 3.0]), 10..21);
+		assert_eq!(visible(100, 0.0, 10.0, [20.0, 30.0]), 100..100);
+		assert_eq!(visible(100, 5.0, 10.0, [0.0, 1.0]), 0..1);
+	}
+
+	#[test]
+	fn decimate_keeps_everything_when_few_samples() {
+		let channel = [0.0, 1.0, -1.0, 0.5];
+		let points = decimate(&channel, 0.0, 1.0, [0.0, 3.0], 10);
+		assert_eq!(points, vec![(0.0, 0.0), (1.0, 1.0), (2.0, -1.0), (3.0, 0.5)]);
+		assert_eq!(decimate(&channel, 0.0, 1.0, [0.0, 3.0], 0).len(), channel.len());
+	}
+
+	#[test]
+	fn decimate_keeps_peaks_of_every_column() {
+		let mut channel = vec![0.0; 1000];
+		channel[123] = 0.9;
+		channel[456] = -0.7;
+		channel[789] = 0.3;
+		let points = decimate(&channel, 0.0, 1.0, [0.0, 999.0], 10);
+		assert!(points.len() <= 10 * 2 + 2);
+		for peak in [(123.0, 0.9), (456.0, -0.7), (789.0, 0.3)] {
+			assert!(points.contains(&peak), "{peak:?} missing");
 		}
+		assert!(points.windows(2).all(|w| w[0].0 < w[1].0), "points out of order");
+	}
+
+	#[test]
+	fn decimate_keeps_samples_next_to_view() {
+		let channel : Vec<f64> = (0..1000).map(|i| i as f64).collect();
+		let points = decimate(&channel, 0.0, 1.0, [100.0, 500.0], 10);
+		assert_eq!(points.first(), Some(&(99.0, 99.0)));
+		assert_eq!(points.last(), Some(&(501.0, 501.0)));
 	}
 }
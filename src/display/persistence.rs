@@ -0,0 +1,216 @@
+use ratatui::{buffer::Buffer, layout::Rect, style::{Color, Style}, widgets::{GraphType, Widget}};
+
+use super::{theme::rgb, DataSet, Dimension, DisplayMode, GraphConfig};
+
+/// dots dimmer than this, relative to brightest one, are not drawn
+const THRESHOLD: f64 = 0.02;
+
+/// braille bit of each dot in a cell, by row and column
+const DOTS: [[u16; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// colors for how often dots are hit, from faintest to brightest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Colormap {
+	#[default]
+	Viridis,
+	Inferno,
+	/// green phosphor of old oscilloscopes
+	Phosphor,
+}
+
+impl Colormap {
+	pub fn next(self) -> Self {
+		match self {
+			Colormap::Viridis => Colormap::Inferno,
+			Colormap::Inferno => Colormap::Phosphor,
+			Colormap::Phosphor => Colormap::Viridis,
+		}
+	}
+
+	/// evenly spaced colors, interpolated in between
+	fn stops(self) -> &'static [(u8, u8, u8)] {
+		match self {
+			Colormap::Viridis => &[(68, 1, 84), (59, 82, 139), (33, 145, 140), (94, 201, 98), (253, 231, 37)],
+			Colormap::Inferno => &[(40, 11, 84), (101, 21, 110), (188, 55, 84), (249, 142, 9), (252, 255, 164)],
+			Colormap::Phosphor => &[(0, 64, 16), (0, 128, 32), (16, 192, 48), (96, 255, 96), (224, 255, 224)],
+		}
+	}
+
+	/// color of given level, from 0 to 1
+	pub fn color(self, level: f64) -> Color {
+		let stops = self.stops();
+		let x = level.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+		let i = (x as usize).min(stops.len() - 2);
+		let t = x - i as f64;
+		let (a, b) = (stops[i], stops[i + 1]);
+		let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+		Color::Rgb(mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+	}
+}
+
+impl std::fmt::Display for Colormap {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Colormap::Viridis => write!(f, "viridis"),
+			Colormap::Inferno => write!(f, "inferno"),
+			Colormap::Phosphor => write!(f, "phosphor"),
+		}
+	}
+}
+
+/// traces hit counted on a grid of braille dots and drawn by intensity. with persistence hits
+/// accumulate over frames, fading with time like phosphor on a CRT, otherwise only last frame
+/// counts. each trace color has its own layer and cells show the brightest one, unless drawn as
+/// heatmap, where all layers add up and intensity picks a color from colormap
+#[derive(Debug, Clone)]
+pub struct Persistence {
+	pub enabled: bool,
+	pub heatmap: bool,
+	pub colormap: Colormap,
+	/// seconds for brightness to fall to about a third
+	pub decay: f64,
+	/// never fade, keep everything drawn since last cleared
+	pub infinite: bool,
+	/// plot area and view of accumulated hits, which are cleared if they change
+	area: Rect,
+	view: [f64; 4],
+	layers: Vec<(Color, Vec<f64>)>,
+	/// seconds of app clock at last update
+	last: Option<f64>,
+}
+
+impl Default for Persistence {
+	fn default() -> Self {
+		Persistence {
+			enabled: false,
+			heatmap: false,
+			colormap: Colormap::default(),
+			decay: 0.5,
+			infinite: false,
+			area: Rect::default(),
+			view: [0.0; 4],
+			layers: Vec::new(),
+			last: None,
+		}
+	}
+}
+
+impl Persistence {
+	/// whether traces should be drawn by intensity at all
+	pub fn active(&self) -> bool {
+		self.enabled || self.heatmap
+	}
+
+	pub fn clear(&mut self) {
+		self.layers.clear();
+		self.last = None;
+	}
+
+	/// fade accumulated hits by time passed since last update, given current time in seconds, then add given traces,
+	/// plotted inside given area. paused frames are the same data again, so nothing changes
+	pub fn update(&mut self, display: &dyn DisplayMode, cfg: &GraphConfig, area: Rect, traces: &[DataSet], now: f64) {
+		let [x0, x1] = display.view(cfg, Dimension::X);
+		let [y0, y1] = display.view(cfg, Dimension::Y);
+		if area != self.area || [x0, x1, y0, y1] != self.view {
+			self.clear();
+			self.area = area;
+			self.view = [x0, x1, y0, y1];
+		}
+		if cfg.pause { return }
+
+		let fade = match self.last {
+			_ if !self.enabled => 0.0, // only hits of this frame
+			Some(last) if !self.infinite => (-(now - last).max(0.0) / self.decay.max(0.001)).exp(),
+			_ => 1.0,
+		};
+		if fade < 1.0 {
+			for (_, dots) in self.layers.iter_mut() {
+				dots.iter_mut().for_each(|x| *x *= fade);
+			}
+		}
+		self.last = Some(now);
+
+		let (columns, rows) = (area.width as usize * 2, area.height as usize * 4);
+		if columns == 0 || rows == 0 { return }
+		let to_dot = |(x, y): (f64, f64)| ((x - x0) / (x1 - x0) * columns as f64, (y1 - y) / (y1 - y0) * rows as f64);
+		for trace in traces {
+			let dots = match self.layers.iter().position(|(color, _)| *color == trace.color) {
+				Some(i) => &mut self.layers[i].1,
+				None => {
+					self.layers.push((trace.color, vec![0.0; columns * rows]));
+					&mut self.layers.last_mut().expect("just pushed").1
+				},
+			};
+			let mut hit = |x: f64, y: f64, weight: f64| {
+				if x >= 0.0 && y >= 0.0 && (x as usize) < columns && (y as usize) < rows {
+					dots[y as usize * columns + x as usize] += weight;
+				}
+			};
+			match trace.graph_type {
+				GraphType::Line => for pair in trace.data.windows(2) {
+					// same total weight for every segment, like time a beam spends on it: fast edges are dimmer
+					let ((ax, ay), (bx, by)) = (to_dot(pair[0]), to_dot(pair[1]));
+					let steps = (bx - ax).abs().max((by - ay).abs()).ceil().clamp(1.0, (columns + rows) as f64 * 4.0);
+					for i in 0..steps as usize {
+						let t = i as f64 / steps;
+						hit(ax + (bx - ax) * t, ay + (by - ay) * t, 1.0 / steps);
+					}
+				},
+				_ => for point in trace.data.iter() {
+					let (x, y) = to_dot(*point);
+					hit(x, y, 1.0);
+				},
+			}
+		}
+	}
+}
+
+impl Widget for &Persistence {
+	fn render(self, area: Rect, buf: &mut Buffer) {
+		let area = area.intersection(self.area);
+		let columns = self.area.width as usize * 2;
+		let summed : Vec<f64>;
+		let layers : Vec<(Option<Color>, &[f64])> = match self.heatmap {
+			true => {
+				summed = (0..columns * self.area.height as usize * 4)
+					.map(|i| self.layers.iter().map(|(_, dots)| dots[i]).sum())
+					.collect();
+				vec![(None, summed.as_slice())]
+			},
+			false => self.layers.iter().map(|(color, dots)| (Some(*color), dots.as_slice())).collect(),
+		};
+		let brightest = layers.iter().flat_map(|(_, dots)| dots.iter()).copied().fold(0.0, f64::max);
+		if brightest <= 0.0 { return }
+		for row in area.top()..area.bottom() {
+			for column in area.left()..area.right() {
+				let (cx, cy) = ((column - self.area.x) as usize * 2, (row - self.area.y) as usize * 4);
+				let mut bits = 0u16;
+				let mut best : Option<(Option<Color>, f64)> = None;
+				for (color, dots) in &layers {
+					for (dy, line) in DOTS.iter().enumerate() {
+						for (dx, bit) in line.iter().enumerate() {
+							let level = dots[(cy + dy) * columns + cx + dx] / brightest;
+							if level < THRESHOLD { continue }
+							bits |= bit;
+							if best.map_or(true, |(_, b)| level > b) { best = Some((*color, level)) }
+						}
+					}
+				}
+				let color = match best {
+					Some((Some(color), level)) => dimmed(color, level),
+					Some((None, level)) => self.colormap.color(level.sqrt()),
+					None => continue,
+				};
+				let symbol = char::from_u32(0x2800 + bits as u32).unwrap_or(' ');
+				buf.get_mut(column, row).set_char(symbol).set_style(Style::default().fg(color));
+			}
+		}
+	}
+}
+
+/// given color at given brightness, from 0 to 1. kept a bit lit so faint traces stay visible
+fn dimmed(color: Color, level: f64) -> Color {
+	let (r, g, b) = rgb(color);
+	let k = 0.25 + 0.75 * level.clamp(0.0, 1.0).sqrt();
+	Color::Rgb((r as f64 * k) as u8, (g as f64 * k) as u8, (b as f64 * k) as u8)
+}
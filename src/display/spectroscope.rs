@@ -1,27 +1,73 @@
 use std::collections::VecDeque;
 
-use crossterm::event::{Event, KeyCode};
-use ratatui::{widgets::{Axis, GraphType}, style::Style, text::Span};
+use ratatui::widgets::GraphType;
 
-use crate::{app::update_value_i, input::Matrix};
+use crate::{app::update_value_i, input::{Matrix, StreamOptions}, keymap::{Action, Control}, music::{Note, Tone, TONES}};
 
-use super::{DisplayMode, GraphConfig, DataSet, Dimension};
+use super::{DisplayMode, GraphConfig, DataSet, Dimension, Cursors, Readout, Measurement, measure::Unit, spectrum::{dominant_bin, magnitudes}, ticks, step_125, DIVISIONS};
 
-use rustfft::{FftPlanner, num_complex::Complex};
+use rustfft::FftPlanner;
 
-#[derive(Default)]
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Spectroscope {
+	#[serde(skip)]
 	pub sampling_rate: u32,
+	#[serde(skip)]
 	pub buffer_size: u32,
 	pub average: u32,
+	#[serde(skip)]
 	pub buf: Vec<VecDeque<Vec<f64>>>,
 	pub window: bool,
 	pub log_y: bool,
+	pub grid: Grid,
 }
 
-fn magnitude(c: Complex<f64>) -> f64 {
-	let squared = (c.re * c.re) + (c.im * c.im);
-	squared.sqrt()
+/// where spectroscope reference lines go
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Grid {
+	/// 1-2-5 steps in each decade, or evenly spaced when zoomed in
+	#[default]
+	Decades,
+	/// C of every octave
+	Octaves,
+	/// every note
+	Semitones,
+}
+
+impl Grid {
+	fn next(self) -> Self {
+		match self {
+			Grid::Decades => Grid::Octaves,
+			Grid::Octaves => Grid::Semitones,
+			Grid::Semitones => Grid::Decades,
+		}
+	}
+}
+
+impl std::fmt::Display for Grid {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Grid::Decades => write!(f, "decades"),
+			Grid::Octaves => write!(f, "octaves"),
+			Grid::Semitones => write!(f, "semitones"),
+		}
+	}
+}
+
+/// frequency with k suffix above 1kHz, short enough for axis labels
+fn hertz(freq: f64) -> String {
+	if freq >= 1000.0 {
+		format!("{:.1}k", freq / 1000.0).replace(".0k", "k")
+	} else {
+		format!("{:.0}", freq)
+	}
+}
+
+/// natural log of magnitude, as decibels
+fn decibels(ln: f64) -> f64 {
+	ln * 20.0 / std::f64::consts::LN_10
 }
 
 // got this from https://github.com/phip1611/spectrum-analyzer/blob/3c079ec2785b031d304bb381ff5f5fe04e6bcf71/src/windows.rs#L40
@@ -37,14 +83,25 @@ pub fn hann_window(samples: &[f64]) -> Vec<f64> {
 	windowed_samples
 }
 
-impl DisplayMode for Spectroscope {
-	fn from_args(opts: &crate::cfg::SourceOptions) -> Self {
+impl Default for Spectroscope {
+	fn default() -> Self {
 		Spectroscope {
-			sampling_rate: opts.sample_rate,
-			buffer_size: opts.buffer,
+			sampling_rate: 48000,
+			buffer_size: 2048,
 			average: 1, buf: Vec::new(),
 			window: false,
 			log_y: true,
+			grid: Grid::default(),
+		}
+	}
+}
+
+impl DisplayMode for Spectroscope {
+	fn from_options(opts: &StreamOptions) -> Self {
+		Spectroscope {
+			sampling_rate: opts.sample_rate,
+			buffer_size: opts.buffer,
+			..Default::default()
 		}
 	}
 
@@ -75,20 +132,32 @@ impl DisplayMode for Spectroscope {
 		}
 	}
 
-	fn axis(&self, cfg: &GraphConfig, dimension: Dimension) -> Axis {
-		let (name, bounds) = match dimension {
-			Dimension::X => ("frequency -", [20.0f64.ln(), ((cfg.samples as f64 / cfg.width as f64) * 20000.0).ln()]),
-			Dimension::Y => (
-				if self.log_y { "| level" } else { "| amplitude" },
-				[if self.log_y { 0. } else { 0.0 }, cfg.scale * 7.5] // very arbitrary but good default
-			),
-			// TODO super arbitraty! wtf! also ugly inline ifs, get this thing together!
-		};
-		let mut a = Axis::default();
-		if cfg.show_ui { // TODO don't make it necessary to check show_ui inside here
-			a = a.title(Span::styled(name, Style::default().fg(cfg.labels_color)));
+	fn axis_name(&self, dimension: Dimension) -> &'static str {
+		match dimension {
+			Dimension::X => "frequency -",
+			Dimension::Y => if self.log_y { "| level" } else { "| amplitude" },
 		}
-		a.style(Style::default().fg(cfg.axis_color)).bounds(bounds)
+	}
+
+	fn bounds(&self, cfg: &GraphConfig, dimension: Dimension) -> [f64; 2] {
+		match dimension {
+			Dimension::X => [20.0f64.ln(), ((cfg.samples as f64 / cfg.width as f64) * 20000.0).ln()],
+			Dimension::Y => [0.0, cfg.scale * 7.5], // very arbitrary but good default
+			// TODO super arbitraty! wtf! get this thing together!
+		}
+	}
+
+	/// X is logarithmic, upper bound grows linearly with samples
+	fn zoom(&self, cfg: &mut GraphConfig, dimension: Dimension, range: [f64; 2]) {
+		match dimension {
+			Dimension::X => {
+				let upper = 20.0 * (range[1] - range[0]).exp();
+				let octave = (cfg.width as f64 * 40.0 / 20000.0).ceil() as u32; // narrower would round past lower bound
+				cfg.samples = ((cfg.width as f64 * upper / 20000.0) as u32).clamp(octave, cfg.width * 2);
+			},
+			Dimension::Y => cfg.scale = ((range[1] - range[0]) / 7.5).clamp(0.0001, 10.0),
+		}
+		self.center(cfg, dimension, range);
 	}
 
 	fn process(&mut self, cfg: &GraphConfig, data: &Matrix<f64>) -> Vec<DataSet> {
@@ -107,25 +176,19 @@ impl DisplayMode for Spectroscope {
 
 		let mut out = Vec::new();
 		let mut planner: FftPlanner<f64> = FftPlanner::new();
-		let sample_len = self.buffer_size * self.average;
-		let resolution = self.sampling_rate as f64 / sample_len as f64;
-		let fft = planner.plan_fft_forward(sample_len as usize);
 
 		for (n, chan_queue) in self.buf.iter().enumerate().rev() {
-			let mut chunk = chan_queue.iter().flatten().copied().collect::<Vec<f64>>();
-			if self.window {
-				chunk = hann_window(chunk.as_slice());
-			}
-			let mut max_val = *chunk.iter().max_by(|a, b| a.total_cmp(b)).expect("empty dataset?");
-			if max_val < 1. { max_val = 1.; }
-			let mut tmp : Vec<Complex<f64>> = chunk.iter().map(|x| Complex { re: *x / max_val, im: 0.0 }).collect();
-			fft.process(tmp.as_mut_slice());
+			let chunk = chan_queue.iter().flatten().copied().collect::<Vec<f64>>();
+			// queue may not be full yet (or chunks may be shorter than buffer), size fft on what we have
+			let resolution = self.sampling_rate as f64 / chunk.len() as f64;
+			let fft = planner.plan_fft_forward(chunk.len());
+			let max_val = chunk.iter().copied().fold(1.0, f64::max);
 			out.push(DataSet::new(
 				Some(self.channel_name(n)),
-				tmp[..=tmp.len() / 2]
-					.iter()
+				magnitudes(&chunk, &fft, self.window)
+					.into_iter()
 					.enumerate()
-					.map(|(i,x)| ((i as f64 * resolution).ln(), if self.log_y { magnitude(*x).ln() } else { magnitude(*x) }))
+					.map(|(i, x)| ((i as f64 * resolution).ln(), if self.log_y { (x / max_val).ln() } else { x / max_val }))
 					.collect(),
 				cfg.marker_type,
 				if cfg.scatter { GraphType::Scatter } else { GraphType::Line },
@@ -136,53 +199,179 @@ impl DisplayMode for Spectroscope {
 		out
 	}
 
-	fn handle(&mut self, event: Event) {
-		if let Event::Key(key) = event {
-			match key.code {
-				KeyCode::PageUp   => update_value_i(&mut self.average, true, 1, 1., 1..65535),
-				KeyCode::PageDown => update_value_i(&mut self.average, false, 1, 1., 1..65535),
-				KeyCode::Char('w') => self.window = !self.window,
-				KeyCode::Char('l') => self.log_y = !self.log_y,
-				_ => {}
+	/// from averaged buffers, always windowed. levels are relative to full scale sine
+	fn measure(&self, _cfg: &GraphConfig, _data: &Matrix<f64>) -> Vec<Measurement> {
+		let mut planner: FftPlanner<f64> = FftPlanner::new();
+		let (mut dominant, mut thd, mut floor) = (Vec::new(), Vec::new(), Vec::new());
+		for chan_queue in &self.buf {
+			let chunk = chan_queue.iter().flatten().copied().collect::<Vec<f64>>();
+			if chunk.len() < 4 {
+				dominant.push(None); thd.push(None); floor.push(None);
+				continue;
 			}
+			let fft = planner.plan_fft_forward(chunk.len());
+			let scale = 4.0 / chunk.len() as f64; // half of energy is in negative bins, and hann window halves the rest
+			let spectrum : Vec<f64> = magnitudes(&chunk, &fft, true).into_iter().map(|x| x * scale).collect();
+			let bin = dominant_bin(&spectrum);
+			dominant.push(bin.map(|x| x * self.sampling_rate as f64 / chunk.len() as f64));
+			thd.push(bin.and_then(|x| harmonic_distortion(&spectrum, x)));
+			floor.push(noise_floor(&spectrum));
 		}
+		vec![
+			Measurement::new("dominant", Unit::Hertz, dominant),
+			Measurement::new("thd", Unit::Percent, thd),
+			Measurement::new("floor", Unit::Decibel, floor),
+		]
 	}
 
-	fn references(&self, cfg: &GraphConfig) -> Vec<DataSet> {
-		let lower = 0.; // if self.log_y { -(cfg.scale * 5.) } else { 0. };
-		let upper = cfg.scale * 7.5;
+	/// X is log of frequency, Y is log of magnitude in log mode, shown in decibels
+	fn labels(&self, cfg: &GraphConfig, dimension: Dimension) -> Vec<String> {
+		let ticks = ticks(self.view(cfg, dimension), DIVISIONS[dimension as usize]);
+		match dimension {
+			Dimension::X => ticks.into_iter().map(|x| hertz(x.exp())).collect(),
+			Dimension::Y if self.log_y => ticks.into_iter().map(|x| format!("{:.0}dB", decibels(x))).collect(),
+			Dimension::Y => ticks.into_iter().map(|x| format!("{:.1}", x)).collect(),
+		}
+	}
+
+	/// X divisions are ratios of frequencies, not worth showing
+	fn division(&self, cfg: &GraphConfig, dimension: Dimension) -> Option<String> {
+		let [a, b] = self.view(cfg, dimension);
+		match dimension {
+			Dimension::X => None,
+			Dimension::Y if self.log_y => Some(format!("{:.1}dB", decibels(b - a) / DIVISIONS[1])),
+			Dimension::Y => Some(format!("{:.3}", (b - a) / DIVISIONS[1])),
+		}
+	}
+
+	/// X is log of frequency. vertical cursors also read first channel level and nearest note
+	fn readout(&self, _cfg: &GraphConfig, cursors: &Cursors, datasets: &[DataSet]) -> Vec<Readout> {
+		let first = self.channel_name(0);
+		let spectrum = datasets.iter().find(|x| x.name.as_deref() == Some(first.as_str()));
+		let mut out = Vec::new();
+		for (i, x) in cursors.x.iter().enumerate() {
+			let Some(x) = x else { continue };
+			let freq = x.exp();
+			out.push(Readout::new(format!("f{}", i + 1), format!("{:.1}Hz", freq)).of(i));
+			if let Some((note, cents)) = Note::nearest(freq) {
+				out.push(Readout::new("note", format!("{} {:+.0}c", note, cents)).of(i));
+			}
+			if let Some(ds) = spectrum.filter(|ds| !ds.data.is_empty()) {
+				let at = ds.data.partition_point(|(f, _)| f < x).min(ds.data.len() - 1);
+				out.push(Readout::new(first.clone(), format!("{:.3}", ds.data[at].1)).of(i));
+			}
+		}
+		if let (Some(a), Some(b)) = (cursors.x[0], cursors.x[1]) {
+			out.push(Readout::new("Δf", format!("{:.1}Hz", b.exp() - a.exp())));
+		}
+		for (i, y) in cursors.y.iter().enumerate() {
+			if let Some(y) = y { out.push(Readout::new(format!("a{}", i + 1), format!("{:.3}", y)).of(i + 2)) }
+		}
+		if let Some(da) = cursors.delta(Dimension::Y) {
+			out.push(Readout::new("Δa", format!("{:.3}", da)));
+		}
+		out
+	}
+
+	fn controls(&self) -> Vec<Control> {
 		vec![
-			DataSet::new(None, vec![(0.0, 0.0), ((cfg.samples as f64).ln(), 0.0)], cfg.marker_type, GraphType::Line, cfg.axis_color), 
-
-			// TODO can we auto generate these? lol...
-			DataSet::new(None, vec![(20.0f64.ln(), lower), (20.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(30.0f64.ln(), lower), (30.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(40.0f64.ln(), lower), (40.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(50.0f64.ln(), lower), (50.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(60.0f64.ln(), lower), (60.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(70.0f64.ln(), lower), (70.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(80.0f64.ln(), lower), (80.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(90.0f64.ln(), lower), (90.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(100.0f64.ln(), lower), (100.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(200.0f64.ln(), lower), (200.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(300.0f64.ln(), lower), (300.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(400.0f64.ln(), lower), (400.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(500.0f64.ln(), lower), (500.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(600.0f64.ln(), lower), (600.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(700.0f64.ln(), lower), (700.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(800.0f64.ln(), lower), (800.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(900.0f64.ln(), lower), (900.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(1000.0f64.ln(), lower), (1000.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(2000.0f64.ln(), lower), (2000.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(3000.0f64.ln(), lower), (3000.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(4000.0f64.ln(), lower), (4000.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(5000.0f64.ln(), lower), (5000.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(6000.0f64.ln(), lower), (6000.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(7000.0f64.ln(), lower), (7000.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(8000.0f64.ln(), lower), (8000.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(9000.0f64.ln(), lower), (9000.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(10000.0f64.ln(), lower), (10000.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
-			DataSet::new(None, vec![(20000.0f64.ln(), lower), (20000.0f64.ln(), upper)], cfg.marker_type, GraphType::Line, cfg.axis_color),
+			Control::new(&[Action::AverageUp, Action::AverageDown], "averaged buffers", self.average),
+			Control::toggle(Action::Window, "hann window", self.window),
+			Control::toggle(Action::LogY, "log scale", self.log_y),
+			Control::new(&[Action::Grid], "reference lines", self.grid),
 		]
 	}
+
+	fn perform(&mut self, action: Action, _magnitude: f64) {
+		match action {
+			Action::AverageUp   => update_value_i(&mut self.average, true, 1, 1., 1..65535),
+			Action::AverageDown => update_value_i(&mut self.average, false, 1, 1., 1..65535),
+			Action::Window      => self.window = !self.window,
+			Action::LogY        => self.log_y = !self.log_y,
+			Action::Grid        => self.grid = self.grid.next(),
+			_ => {}
+		}
+	}
+
+	fn references(&self, cfg: &GraphConfig) -> Vec<DataSet> {
+		let [x0, x1] = self.view(cfg, Dimension::X);
+		let [y0, y1] = self.view(cfg, Dimension::Y);
+		let line = |data| DataSet::new(None, data, cfg.marker_type, GraphType::Line, cfg.axis_color);
+		let mut out = vec![line(vec![(x0, 0.0), (x1, 0.0)])];
+		for (freq, _) in self.grid_lines(cfg) {
+			out.push(line(vec![(freq.ln(), y0), (freq.ln(), y1)]));
+		}
+		out
+	}
+
+	/// grid lines labeled at the top of the view
+	fn annotations(&self, cfg: &GraphConfig) -> Vec<(f64, f64, String)> {
+		let [_, top] = self.view(cfg, Dimension::Y);
+		self.grid_lines(cfg).into_iter().map(|(freq, label)| (freq.ln(), top, label)).collect()
+	}
+}
+
+impl Spectroscope {
+	/// frequencies of reference lines in view, with their labels
+	fn grid_lines(&self, cfg: &GraphConfig) -> Vec<(f64, String)> {
+		let [x0, x1] = self.view(cfg, Dimension::X);
+		let (low, high) = (x0.exp(), x1.exp());
+		let mut out = Vec::new();
+		match self.grid {
+			Grid::Decades if high / low >= 10.0 => { // 1-2-5 in each decade
+				let mut freq = step_125(low, false);
+				if freq < low { freq = step_125(freq, true) }
+				while freq <= high {
+					out.push((freq, hertz(freq)));
+					freq = step_125(freq, true);
+				}
+			},
+			Grid::Decades => { // zoomed in, evenly spaced
+				let step = step_125((high - low) / 5.0, true);
+				let mut freq = (low / step).ceil() * step;
+				while freq <= high {
+					out.push((freq, hertz(freq)));
+					freq += step;
+				}
+			},
+			Grid::Octaves | Grid::Semitones => {
+				for octave in 0..=10 {
+					for tone in TONES.iter() {
+						if self.grid == Grid::Octaves && *tone != Tone::C { continue }
+						let freq = tone.freq(octave) as f64;
+						if freq >= low && freq <= high {
+							out.push((freq, format!("{}{}", tone, octave)));
+						}
+					}
+				}
+			},
+		}
+		out
+	}
+}
+
+/// loudest bin within one of given one, to account for window leakage
+fn peak_near(spectrum: &[f64], bin: usize) -> f64 {
+	spectrum[bin.saturating_sub(1)..(bin + 2).min(spectrum.len())].iter().copied().fold(0.0, f64::max)
+}
+
+/// total harmonic distortion in percent, from harmonics up to the 10th that fit in spectrum
+fn harmonic_distortion(spectrum: &[f64], fundamental: f64) -> Option<f64> {
+	let level = peak_near(spectrum, fundamental.round() as usize);
+	if level <= f64::EPSILON { return None }
+	let harmonics = (2..=10)
+		.map(|k| (fundamental * k as f64).round() as usize)
+		.take_while(|bin| bin + 1 < spectrum.len())
+		.map(|bin| peak_near(spectrum, bin).powi(2))
+		.sum::<f64>();
+	Some(100.0 * harmonics.sqrt() / level)
+}
+
+/// median level of all bins, in dB
+fn noise_floor(spectrum: &[f64]) -> Option<f64> {
+	let mut sorted = spectrum.to_vec();
+	sorted.sort_by(|a, b| a.total_cmp(b));
+	let median = *sorted.get(sorted.len() / 2)?;
+	if median <= 0.0 { return None }
+	Some(20.0 * median.log10())
 }
@@ -0,0 +1,27 @@
+use std::sync::Arc;
+
+use rustfft::{num_complex::Complex, Fft};
+
+use super::spectroscope::hann_window;
+
+/// magnitudes of positive frequency bins, optionally after applying a hann window
+pub fn magnitudes(samples: &[f64], fft: &Arc<dyn Fft<f64>>, window: bool) -> Vec<f64> {
+	let windowed = if window { hann_window(samples) } else { samples.to_vec() };
+	let mut buf : Vec<Complex<f64>> = windowed.into_iter()
+		.map(|x| Complex { re: x, im: 0.0 })
+		.collect();
+	fft.process(&mut buf);
+	buf[..buf.len() / 2].iter().map(|x| x.norm()).collect()
+}
+
+/// loudest bin, skipping dc, refined with parabolic interpolation between its neighbours
+pub fn dominant_bin(spectrum: &[f64]) -> Option<f64> {
+	let (bin, max) = spectrum.iter().enumerate()
+		.skip(1)
+		.max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+	if *max <= f64::EPSILON { return None } // silence
+	if bin + 1 >= spectrum.len() { return Some(bin as f64) }
+	let (a, b, c) = (spectrum[bin - 1].ln(), max.ln(), spectrum[bin + 1].ln());
+	let offset = 0.5 * (a - c) / (a - 2.0 * b + c);
+	Some(bin as f64 + if offset.is_finite() { offset } else { 0.0 })
+}
@@ -0,0 +1,145 @@
+use ratatui::style::Color;
+
+use super::GraphConfig;
+
+/// colors used to draw graphs and ui. in configuration files, colors can be
+/// names, "#rrggbb" or 256-color indexes, and unset ones come from dark theme
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+	/// one color per channel, cycled if there are more channels
+	pub palette: Vec<Color>,
+	pub axis: Color,
+	pub labels: Color,
+}
+
+impl Default for Theme {
+	fn default() -> Self {
+		Theme::dark()
+	}
+}
+
+impl Theme {
+	pub fn dark() -> Self {
+		Theme {
+			palette: vec![Color::Red, Color::Yellow, Color::Green, Color::Magenta],
+			axis: Color::DarkGray,
+			labels: Color::Cyan,
+		}
+	}
+
+	/// darker colors, readable on white backgrounds
+	pub fn light() -> Self {
+		Theme {
+			palette: vec![Color::Indexed(160), Color::Indexed(25), Color::Indexed(28), Color::Indexed(90)],
+			axis: Color::Indexed(248),
+			labels: Color::Indexed(236),
+		}
+	}
+
+	/// Okabe-Ito palette, distinguishable with all common color vision deficiencies
+	pub fn colorblind() -> Self {
+		Theme {
+			palette: vec![
+				Color::Rgb(230, 159, 0),   // orange
+				Color::Rgb(86, 180, 233),  // sky blue
+				Color::Rgb(0, 158, 115),   // bluish green
+				Color::Rgb(204, 121, 167), // reddish purple
+				Color::Rgb(240, 228, 66),  // yellow
+				Color::Rgb(0, 114, 178),   // blue
+				Color::Rgb(213, 94, 0),    // vermillion
+			],
+			axis: Color::DarkGray,
+			labels: Color::Gray,
+		}
+	}
+
+	/// terminal default foreground for everything
+	pub fn monochrome() -> Self {
+		Theme {
+			palette: vec![Color::Reset],
+			axis: Color::Reset,
+			labels: Color::Reset,
+		}
+	}
+
+	/// shades of green, like an old crt
+	pub fn phosphor() -> Self {
+		Theme {
+			palette: vec![Color::Rgb(51, 255, 51), Color::Rgb(0, 190, 0), Color::Rgb(150, 255, 150), Color::Rgb(0, 130, 0)],
+			axis: Color::Rgb(0, 90, 0),
+			labels: Color::Rgb(51, 255, 51),
+		}
+	}
+
+	/// all built-in themes, by name
+	pub fn builtin() -> Vec<(String, Theme)> {
+		vec![
+			("dark".into(), Theme::dark()),
+			("light".into(), Theme::light()),
+			("colorblind".into(), Theme::colorblind()),
+			("monochrome".into(), Theme::monochrome()),
+			("phosphor".into(), Theme::phosphor()),
+		]
+	}
+
+	pub fn apply(&self, cfg: &mut GraphConfig) {
+		if !self.palette.is_empty() { // would break channel colors
+			cfg.palette = self.palette.clone();
+		}
+		cfg.axis_color = self.axis;
+		cfg.labels_color = self.labels;
+	}
+}
+
+/// red, green and blue of terminal colors, using xterm default palette for named and indexed ones
+pub fn rgb(color: Color) -> (u8, u8, u8) {
+	let index = match color {
+		Color::Rgb(r, g, b) => return (r, g, b),
+		Color::Indexed(i) => i,
+		Color::Reset => 7,
+		Color::Black => 0,
+		Color::Red => 1,
+		Color::Green => 2,
+		Color::Yellow => 3,
+		Color::Blue => 4,
+		Color::Magenta => 5,
+		Color::Cyan => 6,
+		Color::Gray => 7,
+		Color::DarkGray => 8,
+		Color::LightRed => 9,
+		Color::LightGreen => 10,
+		Color::LightYellow => 11,
+		Color::LightBlue => 12,
+		Color::LightMagenta => 13,
+		Color::LightCyan => 14,
+		Color::White => 15,
+	};
+	match index {
+		0 => (0, 0, 0),
+		1 => (205, 0, 0),
+		2 => (0, 205, 0),
+		3 => (205, 205, 0),
+		4 => (0, 0, 238),
+		5 => (205, 0, 205),
+		6 => (0, 205, 205),
+		7 => (229, 229, 229),
+		8 => (127, 127, 127),
+		9 => (255, 0, 0),
+		10 => (0, 255, 0),
+		11 => (255, 255, 0),
+		12 => (92, 92, 255),
+		13 => (255, 0, 255),
+		14 => (0, 255, 255),
+		15 => (255, 255, 255),
+		16..=231 => { // 6x6x6 color cube
+			let level = |x: u8| if x == 0 { 0 } else { 55 + x * 40 };
+			let i = index - 16;
+			(level(i / 36), level((i / 6) % 6), level(i % 6))
+		},
+		_ => { // grayscale ramp
+			let l = 8 + (index - 232) * 10;
+			(l, l, l)
+		},
+	}
+}
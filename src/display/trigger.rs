@@ -0,0 +1,362 @@
+/// settings shared by all triggers, read on every sample so they can change live
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriggerParams {
+	pub threshold: f64,
+	pub hysteresis: f64,
+	/// half height of the band around threshold used by window, runt and slope triggers
+	pub band: f64,
+	/// pulse width or transition time, in samples
+	pub width: f64,
+	/// trigger on pulses or transitions longer than width, instead of shorter
+	pub longer: bool,
+	/// look for falling edges and negative pulses
+	pub falling: bool,
+}
+
+impl TriggerParams {
+	/// sample mirrored around threshold when looking for falling edges, so triggers
+	/// only need to handle rising ones
+	fn oriented(&self, x: f64) -> f64 {
+		if self.falling { 2.0 * self.threshold - x } else { x }
+	}
+
+	fn lower(&self) -> f64 {
+		self.threshold - self.band.abs()
+	}
+
+	fn upper(&self) -> f64 {
+		self.threshold + self.band.abs()
+	}
+
+	fn qualifies(&self, duration: f64) -> bool {
+		if self.longer { duration > self.width } else { duration < self.width }
+	}
+}
+
+/// a condition watched sample by sample. triggers keep their own state, across frames too
+pub trait Trigger : std::fmt::Debug {
+	/// feed next sample, returns true if condition is met on it
+	fn step(&mut self, x: f64, params: &TriggerParams) -> bool;
+	fn clone_box(&self) -> Box<dyn Trigger>;
+}
+
+impl Clone for Box<dyn Trigger> {
+	fn clone(&self) -> Self {
+		self.clone_box()
+	}
+}
+
+/// available triggers, as named in configuration files and header
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerKind {
+	/// signal crosses threshold, after going past hysteresis band on the other side
+	#[default]
+	Edge,
+	/// pulse beyond threshold lasts longer or shorter than width
+	Pulse,
+	/// signal leaves the band around threshold
+	Window,
+	/// pulse crosses lower side of the band but falls back without reaching upper side
+	Runt,
+	/// transition across the band takes longer or shorter than width
+	Slope,
+}
+
+impl TriggerKind {
+	pub fn next(self) -> Self {
+		match self {
+			TriggerKind::Edge => TriggerKind::Pulse,
+			TriggerKind::Pulse => TriggerKind::Window,
+			TriggerKind::Window => TriggerKind::Runt,
+			TriggerKind::Runt => TriggerKind::Slope,
+			TriggerKind::Slope => TriggerKind::Edge,
+		}
+	}
+
+	/// a new trigger of this kind, in its initial state
+	pub fn build(self) -> Box<dyn Trigger> {
+		match self {
+			TriggerKind::Edge => Box::<Edge>::default(),
+			TriggerKind::Pulse => Box::<Pulse>::default(),
+			TriggerKind::Window => Box::<Window>::default(),
+			TriggerKind::Runt => Box::<Runt>::default(),
+			TriggerKind::Slope => Box::<Slope>::default(),
+		}
+	}
+}
+
+impl std::fmt::Display for TriggerKind {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			TriggerKind::Edge => write!(f, "edge"),
+			TriggerKind::Pulse => write!(f, "pulse"),
+			TriggerKind::Window => write!(f, "window"),
+			TriggerKind::Runt => write!(f, "runt"),
+			TriggerKind::Slope => write!(f, "slope"),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Edge {
+	armed: bool,
+}
+
+impl Trigger for Edge {
+	fn step(&mut self, x: f64, params: &TriggerParams) -> bool {
+		let x = params.oriented(x);
+		if x < params.threshold - params.hysteresis.abs() {
+			self.armed = true;
+		} else if self.armed && x >= params.threshold {
+			self.armed = false;
+			return true;
+		}
+		false
+	}
+
+	fn clone_box(&self) -> Box<dyn Trigger> {
+		Box::new(self.clone())
+	}
+}
+
+/// triggers when pulse ends, once its width is known
+#[derive(Debug, Clone, Default)]
+pub struct Pulse {
+	/// seen signal out of a pulse, so next one is whole
+	ready: bool,
+	/// samples since pulse started, if inside one
+	inside: Option<usize>,
+}
+
+impl Trigger for Pulse {
+	fn step(&mut self, x: f64, params: &TriggerParams) -> bool {
+		let x = params.oriented(x);
+		let outside = x < params.threshold - params.hysteresis.abs();
+		match self.inside {
+			Some(width) if outside => {
+				self.inside = None;
+				return params.qualifies(width as f64);
+			},
+			Some(width) => self.inside = Some(width + 1),
+			None if outside => self.ready = true,
+			None if self.ready && x >= params.threshold => self.inside = Some(1),
+			None => {},
+		}
+		false
+	}
+
+	fn clone_box(&self) -> Box<dyn Trigger> {
+		Box::new(self.clone())
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Window {
+	inside: bool,
+}
+
+impl Trigger for Window {
+	fn step(&mut self, x: f64, params: &TriggerParams) -> bool {
+		let inside = x >= params.lower() && x <= params.upper();
+		let left = self.inside && !inside;
+		self.inside = inside;
+		left
+	}
+
+	fn clone_box(&self) -> Box<dyn Trigger> {
+		Box::new(self.clone())
+	}
+}
+
+/// triggers when runt pulse falls back below lower side of the band
+#[derive(Debug, Clone, Default)]
+pub struct Runt {
+	ready: bool,
+	/// inside a pulse, and whether it reached upper side of the band
+	pulse: Option<bool>,
+}
+
+impl Trigger for Runt {
+	fn step(&mut self, x: f64, params: &TriggerParams) -> bool {
+		let x = params.oriented(x);
+		if x < params.lower() {
+			self.ready = true;
+			return self.pulse.take() == Some(false);
+		}
+		if x >= params.upper() {
+			// also when jumping over the band in one sample, or falling back through it would look like a runt
+			if self.ready { self.pulse = Some(true) }
+		} else if self.ready && self.pulse.is_none() {
+			self.pulse = Some(false);
+		}
+		false
+	}
+
+	fn clone_box(&self) -> Box<dyn Trigger> {
+		Box::new(self.clone())
+	}
+}
+
+/// triggers when transition reaches upper side of the band, once its duration is known
+#[derive(Debug, Clone, Default)]
+pub struct Slope {
+	/// samples since signal was last below lower side of the band
+	since: Option<usize>,
+}
+
+impl Trigger for Slope {
+	fn step(&mut self, x: f64, params: &TriggerParams) -> bool {
+		let x = params.oriented(x);
+		if x < params.lower() {
+			self.since = Some(0);
+		} else if x >= params.upper() {
+			if let Some(duration) = self.since.take() {
+				return params.qualifies((duration + 1) as f64);
+			}
+		} else if let Some(duration) = &mut self.since {
+			*duration += 1;
+		}
+		false
+	}
+
+	fn clone_box(&self) -> Box<dyn Trigger> {
+		Box::new(self.clone())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn params() -> TriggerParams {
+		TriggerParams { threshold: 0.0, hysteresis: 0.1, band: 0.5, width: 3.0, longer: false, falling: false }
+	}
+
+	/// indexes of samples the trigger fires on
+	fn fires(trigger: &mut dyn Trigger, samples: &[f64], params: &TriggerParams) -> Vec<usize> {
+		samples.iter().enumerate()
+			.filter(|(_, x)| trigger.step(**x, params))
+			.map(|(i, _)| i)
+			.collect()
+	}
+
+	#[test]
+	fn edge_fires_on_rising_crossing() {
+		let mut edge = Edge::default();
+		assert_eq!(fires(&mut edge, &[-1.0, -0.5, 0.0, 0.5, 1.0, -1.0, 1.0], &params()), vec![2, 6]);
+	}
+
+	#[test]
+	fn edge_needs_signal_below_hysteresis_first() {
+		let mut edge = Edge::default();
+		// starting high, and noise dipping less than hysteresis below threshold, never arm it
+		assert_eq!(fires(&mut edge, &[1.0, -0.05, 0.05, -0.05, 0.05], &params()), Vec::<usize>::new());
+		assert_eq!(fires(&mut edge, &[-0.2, 0.05, -0.05, 0.05], &params()), vec![1]);
+	}
+
+	#[test]
+	fn edge_fires_once_per_crossing() {
+		let mut edge = Edge::default();
+		assert_eq!(fires(&mut edge, &[-1.0, 0.5, 0.6, 0.7, 0.8], &params()), vec![1]);
+	}
+
+	#[test]
+	fn edge_falling() {
+		let params = TriggerParams { falling: true, threshold: 0.2, ..params() };
+		let mut edge = Edge::default();
+		assert_eq!(fires(&mut edge, &[-1.0, 1.0, 0.5, 0.2, -1.0, 1.0, 0.0], &params), vec![3, 6]);
+	}
+
+	#[test]
+	fn edge_negative_hysteresis_counts_as_positive() {
+		let params = TriggerParams { hysteresis: -0.1, ..params() };
+		let mut edge = Edge::default();
+		assert_eq!(fires(&mut edge, &[-0.05, 0.5, -0.2, 0.5], &params), vec![3]);
+	}
+
+	#[test]
+	fn pulse_fires_at_end_of_qualifying_pulse() {
+		let samples = [-1.0, 1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, -1.0];
+		assert_eq!(fires(&mut Pulse::default(), &samples, &params()), vec![3]);
+		let longer = TriggerParams { longer: true, ..params() };
+		assert_eq!(fires(&mut Pulse::default(), &samples, &longer), vec![8]);
+	}
+
+	#[test]
+	fn pulse_ignores_pulse_already_going_at_start() {
+		assert_eq!(fires(&mut Pulse::default(), &[1.0, -1.0, 1.0, -1.0], &params()), vec![3]);
+	}
+
+	#[test]
+	fn pulse_width_equal_to_limit_never_qualifies() {
+		let samples = [-1.0, 1.0, 1.0, 1.0, -1.0];
+		assert_eq!(fires(&mut Pulse::default(), &samples, &params()), Vec::<usize>::new());
+		let longer = TriggerParams { longer: true, ..params() };
+		assert_eq!(fires(&mut Pulse::default(), &samples, &longer), Vec::<usize>::new());
+	}
+
+	#[test]
+	fn window_fires_when_leaving_band() {
+		let samples = [0.0, 0.2, 0.7, 0.3, -0.8, -1.0, 0.0];
+		assert_eq!(fires(&mut Window::default(), &samples, &params()), vec![2, 4]);
+	}
+
+	#[test]
+	fn window_ignores_signal_starting_outside() {
+		assert_eq!(fires(&mut Window::default(), &[1.0, 1.0, 0.0], &params()), Vec::<usize>::new());
+	}
+
+	#[test]
+	fn runt_fires_when_pulse_falls_back_short_of_upper_side() {
+		assert_eq!(fires(&mut Runt::default(), &[-1.0, 0.0, 0.2, -1.0], &params()), vec![3]);
+	}
+
+	#[test]
+	fn runt_ignores_full_pulses() {
+		assert_eq!(fires(&mut Runt::default(), &[-1.0, 0.0, 1.0, 0.0, -1.0], &params()), Vec::<usize>::new());
+	}
+
+	#[test]
+	fn runt_ignores_full_pulse_jumping_over_band() {
+		assert_eq!(fires(&mut Runt::default(), &[-1.0, 1.0, 0.0, -1.0], &params()), Vec::<usize>::new());
+		assert_eq!(fires(&mut Runt::default(), &[-1.0, 1.0, -1.0, 0.0, -1.0], &params()), vec![4]);
+	}
+
+	#[test]
+	fn runt_needs_signal_below_band_first() {
+		assert_eq!(fires(&mut Runt::default(), &[0.0, -1.0], &params()), Vec::<usize>::new());
+	}
+
+	#[test]
+	fn runt_falling() {
+		let falling = TriggerParams { falling: true, ..params() };
+		assert_eq!(fires(&mut Runt::default(), &[1.0, 0.0, 1.0, 0.0, -1.0, 1.0], &falling), vec![2]);
+	}
+
+	#[test]
+	fn slope_measures_time_across_band() {
+		let slow = [-1.0, -0.3, 0.0, 0.3, 1.0];
+		assert_eq!(fires(&mut Slope::default(), &slow, &params()), Vec::<usize>::new());
+		let longer = TriggerParams { longer: true, ..params() };
+		assert_eq!(fires(&mut Slope::default(), &slow, &longer), vec![4]);
+		assert_eq!(fires(&mut Slope::default(), &[-1.0, 1.0], &params()), vec![1]);
+	}
+
+	#[test]
+	fn slope_restarts_when_falling_back_below_band() {
+		assert_eq!(fires(&mut Slope::default(), &[-1.0, 0.0, -1.0, 0.0, 1.0], &params()), vec![4]);
+		assert_eq!(fires(&mut Slope::default(), &[1.0, 0.0, 1.0], &params()), Vec::<usize>::new());
+	}
+
+	#[test]
+	fn every_kind_builds_and_cycles_back() {
+		let mut kind = TriggerKind::default();
+		for _ in 0..5 {
+			let mut trigger = kind.build();
+			assert!(!trigger.step(0.0, &params()));
+			kind = kind.next();
+		}
+		assert_eq!(kind, TriggerKind::default());
+	}
+}
@@ -1,14 +1,21 @@
-use ratatui::{widgets::{Axis, GraphType}, style::Style, text::Span};
+use ratatui::widgets::GraphType;
 
-use crate::input::Matrix;
+use crate::{input::{Matrix, StreamOptions}, keymap::{Action, Control}};
 
-use super::{DisplayMode, GraphConfig, DataSet, Dimension};
+use super::{DisplayMode, GraphConfig, DataSet, Dimension, interpolate};
 
-#[derive(Default)]
-pub struct Vectorscope {}
+/// points per sample added by sin(x)/x interpolation
+const UPSAMPLING : usize = 4;
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Vectorscope {
+	/// reconstruct band-limited signal between samples, instead of straight lines
+	pub sinc: bool,
+}
 
 impl DisplayMode for Vectorscope {
-	fn from_args(_opts: &crate::cfg::SourceOptions) -> Self {
+	fn from_options(_opts: &StreamOptions) -> Self {
 		Vectorscope::default()
 	}
 
@@ -24,45 +31,61 @@ impl DisplayMode for Vectorscope {
 		"live".into()
 	}
 
-	fn axis(&self, cfg: &GraphConfig, dimension: Dimension) -> Axis {
-		let (name, bounds) = match dimension {
-			Dimension::X => ("left -", [-cfg.scale, cfg.scale]),
-			Dimension::Y => ("| right", [-cfg.scale, cfg.scale]),
-		};
-		let mut a = Axis::default();
-		if cfg.show_ui { // TODO don't make it necessary to check show_ui inside here
-			a = a.title(Span::styled(name, Style::default().fg(cfg.labels_color)));
+	fn axis_name(&self, dimension: Dimension) -> &'static str {
+		match dimension {
+			Dimension::X => "left -",
+			Dimension::Y => "| right",
+		}
+	}
+
+	fn persistent(&self) -> bool { true }
+
+	fn bounds(&self, cfg: &GraphConfig, _dimension: Dimension) -> [f64; 2] {
+		[-cfg.scale, cfg.scale]
+	}
+
+	/// both axes follow scale, so only Y zooms and X just moves
+	fn zoom(&self, cfg: &mut GraphConfig, dimension: Dimension, range: [f64; 2]) {
+		if dimension == Dimension::Y {
+			cfg.scale = ((range[1] - range[0]) / 2.0).clamp(0.0001, 10.0);
 		}
-		a.style(Style::default().fg(cfg.axis_color)).bounds(bounds)
+		self.center(cfg, dimension, range);
 	}
 
 	fn references(&self, cfg: &GraphConfig) -> Vec<DataSet> {
 		vec![
-			DataSet::new(None, vec![(-cfg.scale, 0.0), (cfg.scale, 0.0)], cfg.marker_type, GraphType::Line, cfg.axis_color), 
-			DataSet::new(None, vec![(0.0, -cfg.scale), (0.0, cfg.scale)], cfg.marker_type, GraphType::Line, cfg.axis_color),
+			DataSet::new(None, self.view(cfg, Dimension::X).map(|x| (x, 0.0)).to_vec(), cfg.marker_type, GraphType::Line, cfg.axis_color),
+			DataSet::new(None, self.view(cfg, Dimension::Y).map(|y| (0.0, y)).to_vec(), cfg.marker_type, GraphType::Line, cfg.axis_color),
 		]
 	}
 
+	fn controls(&self) -> Vec<Control> {
+		vec![
+			Control::toggle(Action::Sinc, "sin(x)/x interpolation", self.sinc),
+		]
+	}
+
+	fn perform(&mut self, action: Action, _magnitude: f64) {
+		if action == Action::Sinc { self.sinc = !self.sinc }
+	}
+
 	fn process(&mut self, cfg: &GraphConfig, data: &Matrix<f64>) -> Vec<DataSet> {
 		let mut out = Vec::new();
 
+		// fractional sample indexes and values of a channel
+		let points = |channel: &[f64]| {
+			let len = channel.len().min(cfg.samples as usize + 1);
+			match self.sinc {
+				true => interpolate::upsample(channel, 0..len, UPSAMPLING),
+				false => channel[..len].iter().enumerate().map(|(i, x)| (i as f64, *x)).collect(),
+			}
+		};
 		for (n, chunk) in data.chunks(2).enumerate() {
-			let mut tmp = vec![];
-			match chunk.len() {
-				2 => {
-					for i in 0..std::cmp::min(chunk[0].len(), chunk[1].len()) {
-						if i > cfg.samples as usize { break }
-						tmp.push((chunk[0][i], chunk[1][i]));
-					}
-				},
-				1 => {
-					for i in 0..chunk[0].len() {
-						if i > cfg.samples as usize { break }
-						tmp.push((chunk[0][i], i as f64));
-					}
-				},
+			let tmp : Vec<(f64, f64)> = match chunk.len() {
+				2 => points(&chunk[0]).into_iter().zip(points(&chunk[1])).map(|((_, x), (_, y))| (x, y)).collect(),
+				1 => points(&chunk[0]).into_iter().map(|(i, x)| (x, i)).collect(),
 				_ => continue,
-			}
+			};
 			// split it in two for easier coloring
 			// TODO configure splitting in multiple parts?
 			let pivot = tmp.len() / 2;
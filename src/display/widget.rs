@@ -0,0 +1,100 @@
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::{Chart, Widget}};
+
+use crate::input::Matrix;
+
+use super::{DataSet, Dimension, DisplayMode, GraphConfig};
+
+/// ratatui widget plotting data through any display mode, to embed scopes in other interfaces
+pub struct Scope<'a> {
+	display: &'a mut dyn DisplayMode,
+	cfg: &'a GraphConfig,
+	data: &'a Matrix<f64>,
+}
+
+impl<'a> Scope<'a> {
+	pub fn new(display: &'a mut dyn DisplayMode, cfg: &'a GraphConfig, data: &'a Matrix<f64>) -> Self {
+		Scope { display, cfg, data }
+	}
+}
+
+impl Widget for Scope<'_> {
+	fn render(self, area: Rect, buf: &mut Buffer) {
+		let datasets = datasets(self.display, self.cfg, self.data);
+		chart(self.display, self.cfg, &datasets).render(area, buf);
+		annotate(self.display, self.cfg, plot_area(self.display, self.cfg, area), buf);
+	}
+}
+
+/// process data with given display mode, adding graticule and its references if enabled
+pub fn datasets(display: &mut dyn DisplayMode, cfg: &GraphConfig, data: &Matrix<f64>) -> Vec<DataSet> {
+	let (mut datasets, mut traces) = layers(display, cfg, data);
+	datasets.append(&mut traces);
+	datasets
+}
+
+/// same as datasets, but keeping graticule and references apart from processed data
+pub fn layers(display: &mut dyn DisplayMode, cfg: &GraphConfig, data: &Matrix<f64>) -> (Vec<DataSet>, Vec<DataSet>) {
+	let mut references = Vec::new();
+	if cfg.graticule {
+		references.append(&mut display.graticule(cfg));
+	}
+	if cfg.references {
+		references.append(&mut display.references(cfg));
+	}
+	(references, display.process(cfg, data))
+}
+
+/// chart of already processed datasets, with axes from given display mode
+pub fn chart<'a>(display: &'a dyn DisplayMode, cfg: &GraphConfig, datasets: impl IntoIterator<Item = &'a DataSet>) -> Chart<'a> {
+	Chart::new(datasets.into_iter().map(|x| x.into()).collect())
+		.x_axis(display.axis(cfg, Dimension::X)) // TODO allow to have axis sometimes?
+		.y_axis(display.axis(cfg, Dimension::Y))
+}
+
+/// part of chart area where data is plotted, to map terminal cells to data coordinates.
+/// leaves room for axis labels the same way ratatui charts do
+pub fn plot_area(display: &dyn DisplayMode, cfg: &GraphConfig, area: Rect) -> Rect {
+	if !cfg.show_ui || area.width == 0 || area.height == 0 { return area } // no labels
+	let x_labels = display.labels(cfg, Dimension::X);
+	let y_labels = display.labels(cfg, Dimension::Y);
+	let (mut left, mut bottom) = (area.left(), area.bottom() - 1);
+
+	// label row, then axis row, if there is room for them
+	let x_rows = if x_labels.is_empty() { 0 } else { 2 };
+	for _ in 0..x_rows {
+		if bottom > area.top() { bottom -= 1 }
+	}
+
+	// widest Y label, or part of first X label left of Y axis, at most a third of the width
+	let has_y = !y_labels.is_empty();
+	let y_width = y_labels.iter().map(|x| x.chars().count()).max().unwrap_or(0);
+	let x_first = x_labels.first().map(|x| x.chars().count().saturating_sub(has_y as usize)).unwrap_or(0);
+	left += (y_width.max(x_first) as u16).min(area.width / 3);
+	if has_y && left + 1 < area.right() { left += 1 }
+
+	Rect::new(left, area.top(), area.right().saturating_sub(left), bottom.saturating_sub(area.top()) + 1)
+}
+
+/// write annotations of given display mode over an already rendered plot area, if references are shown.
+/// labels start right of their point, and are skipped where they would cover text, like axis titles,
+/// legend or previous labels
+pub fn annotate(display: &dyn DisplayMode, cfg: &GraphConfig, plot: Rect, buf: &mut Buffer) {
+	if !cfg.references || !cfg.show_ui || plot.width == 0 || plot.height == 0 { return }
+	let [x0, x1] = display.view(cfg, Dimension::X);
+	let [y0, y1] = display.view(cfg, Dimension::Y);
+	for (x, y, text) in display.annotations(cfg) {
+		let (fx, fy) = ((x - x0) / (x1 - x0), (y1 - y) / (y1 - y0));
+		if !(0.0..1.0).contains(&fx) || !(0.0..=1.0).contains(&fy) { continue }
+		let column = plot.x + (fx * plot.width as f64) as u16 + 1;
+		let row = plot.y + ((fy * plot.height as f64) as u16).min(plot.height - 1);
+		let width = text.chars().count() as u16;
+		if width == 0 || column + width > plot.right() { continue }
+		if (column - 1..column + width + 1).any(|c| c < plot.right() && !plotted(buf.get(c, row).symbol())) { continue }
+		buf.set_string(column, row, text, Style::default().fg(cfg.labels_color));
+	}
+}
+
+/// whether a cell is empty or only has plotted points, so it can be written over
+fn plotted(symbol: &str) -> bool {
+	symbol.chars().all(|c| c == ' ' || ('\u{2800}'..='\u{28FF}').contains(&c) || "•█▀▄".contains(c))
+}
@@ -25,7 +25,8 @@ pub enum AudioDeviceErrors {
 }
 
 impl DefaultAudioDeviceWithCPAL {
-	pub fn new(device: Option<&str>, opts: &crate::cfg::SourceOptions, timeout_secs: u64) -> Result<Box<impl super::DataSource<f64>>, AudioDeviceErrors> {
+	#[allow(clippy::new_ret_no_self)]
+	pub fn new(device: Option<&str>, opts: &super::StreamOptions, timeout_secs: u64) -> Result<Box<impl super::DataSource<f64>>, AudioDeviceErrors> {
 		let host = cpal::default_host();
 		let device = match device {
 			Some(name) => host
@@ -6,7 +6,9 @@ pub struct FileSource {
 	file: File,
 	buffer: Vec<u8>,
 	channels: usize,
+	#[allow(dead_code)] // until rate limiting is implemented
 	sample_rate: usize,
+	#[allow(dead_code)] // until rate limiting is implemented
 	limit_rate: bool,
 	// TODO when all data is available (eg, file) limit data flow to make it
 	// somehow visualizable. must be optional because named pipes block
@@ -15,7 +17,7 @@ pub struct FileSource {
 
 impl FileSource {
 	#[allow(clippy::new_ret_no_self)]
-	pub fn new(path: &str, opts: &crate::cfg::SourceOptions, limit_rate: bool) -> Result<Box<dyn super::DataSource<f64>>, std::io::Error> {
+	pub fn new(path: &str, opts: &super::StreamOptions, limit_rate: bool) -> Result<Box<dyn super::DataSource<f64>>, std::io::Error> {
 		Ok(Box::new(
 			FileSource {
 				channels: opts.channels,
@@ -9,6 +9,15 @@ pub mod cpal;
 
 pub type Matrix<T> = Vec<Vec<T>>;
 
+/// how sources capture audio, and what display modes expect from them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamOptions {
+	pub channels: usize,
+	/// samples of each channel in every frame
+	pub buffer: u32,
+	pub sample_rate: u32,
+}
+
 pub trait DataSource<T> {
 	fn recv(&mut self) -> Option<Matrix<T>>; // TODO convert in Result and make generic error
 }
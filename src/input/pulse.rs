@@ -11,7 +11,7 @@ pub struct PulseAudioSimpleDataSource {
 
 impl PulseAudioSimpleDataSource {
 	#[allow(clippy::new_ret_no_self)]
-	pub fn new(device: Option<&str>, opts: &crate::cfg::SourceOptions, server_buffer: u32) -> Result<Box<dyn super::DataSource<f64>>, PAErr> {
+	pub fn new(device: Option<&str>, opts: &super::StreamOptions, server_buffer: u32) -> Result<Box<dyn super::DataSource<f64>>, PAErr> {
 		let spec = Spec {
 			format: Format::S16NE, // TODO allow more formats?
 			channels: opts.channels as u8,
@@ -0,0 +1,477 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// everything that can be bound to a key. global actions are handled by the app,
+/// the others by the display mode they belong to, which ignores everything else
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+	Quit,
+	Help,
+	Pause,
+	Scatter,
+	ToggleUi,
+	References,
+	Graticule,
+	Snapshot,
+	Theme,
+	NextMode,
+	Reset,
+	YRangeUp,
+	YRangeDown,
+	XRangeUp,
+	XRangeDown,
+	Cursor,
+	CursorForward,
+	CursorBack,
+	ClearCursors,
+	Measurements,
+	MeasureAverageUp,
+	MeasureAverageDown,
+	Persistence,
+	InfinitePersistence,
+	DecayUp,
+	DecayDown,
+	Heatmap,
+	Colormap,
+
+	// oscilloscope
+	Trigger,
+	Edge,
+	Peaks,
+	Sinc,
+	SelectChannel,
+	Invert,
+	Coupling,
+	HideChannel,
+	ThresholdUp,
+	ThresholdDown,
+	HysteresisUp,
+	HysteresisDown,
+	TriggerSource,
+	TriggerLeft,
+	TriggerRight,
+	TriggerMode,
+	Arm,
+	HoldoffUp,
+	HoldoffDown,
+	TriggerType,
+	BandUp,
+	BandDown,
+	WidthUp,
+	WidthDown,
+	Longer,
+
+	// spectroscope
+	AverageUp,
+	AverageDown,
+	Window,
+	LogY,
+	Grid,
+}
+
+/// one line of help: what some actions control and its current value, if any
+#[derive(Debug, Clone)]
+pub struct Control {
+	pub actions: Vec<Action>,
+	pub name: &'static str,
+	pub value: String,
+}
+
+impl Control {
+	pub fn new(actions: &[Action], name: &'static str, value: impl Display) -> Self {
+		Control { actions: actions.to_vec(), name, value: value.to_string() }
+	}
+
+	/// a control without any value to show, like quitting
+	pub fn action(action: Action, name: &'static str) -> Self {
+		Control::new(&[action], name, "")
+	}
+
+	pub fn toggle(action: Action, name: &'static str, value: bool) -> Self {
+		Control::new(&[action], name, if value { "on" } else { "off" })
+	}
+}
+
+/// a key with its modifiers, written like "q", "ctrl+c", "shift+pageup" or "space"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+	pub code: KeyCode,
+	pub modifiers: KeyModifiers,
+}
+
+impl Key {
+	pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+		Key { code, modifiers }.normalized()
+	}
+
+	pub const fn plain(code: KeyCode) -> Self {
+		Key { code, modifiers: KeyModifiers::NONE }
+	}
+
+	/// terminals send shifted letters as uppercase with shift held: fold shift into the letter,
+	/// so "B", "shift+b" and what crossterm reports for them are the same key
+	pub fn normalized(self) -> Self {
+		match self.code {
+			KeyCode::Char(c) if c.is_alphabetic() && (c.is_uppercase() || self.modifiers.contains(KeyModifiers::SHIFT)) => Key {
+				code: KeyCode::Char(c.to_uppercase().next().unwrap_or(c)),
+				modifiers: self.modifiers - KeyModifiers::SHIFT,
+			},
+			_ => self,
+		}
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid key '{0}'")]
+pub struct KeyError(String);
+
+impl FromStr for Key {
+	type Err = KeyError;
+
+	fn from_str(txt: &str) -> Result<Self, Self::Err> {
+		let err = || KeyError(txt.into());
+		// '+' separates modifiers, but can also be the key itself
+		let (mods, key) = match txt.strip_suffix("++") {
+			Some(mods) => (Some(mods), "+"),
+			None if txt == "+" => (None, "+"),
+			None => match txt.rsplit_once('+') {
+				Some((mods, key)) => (Some(mods), key),
+				None => (None, txt),
+			},
+		};
+
+		let mut modifiers = KeyModifiers::NONE;
+		for m in mods.into_iter().flat_map(|x| x.split('+')) {
+			modifiers |= match m.to_lowercase().as_str() {
+				"ctrl" | "control" => KeyModifiers::CONTROL,
+				"shift" => KeyModifiers::SHIFT,
+				"alt" => KeyModifiers::ALT,
+				_ => return Err(err()),
+			};
+		}
+
+		let mut chars = key.chars();
+		let code = match (chars.next(), chars.next()) {
+			(Some(c), None) => KeyCode::Char(c),
+			_ => match key.to_lowercase().as_str() {
+				"space" => KeyCode::Char(' '),
+				"up" => KeyCode::Up,
+				"down" => KeyCode::Down,
+				"left" => KeyCode::Left,
+				"right" => KeyCode::Right,
+				"pageup" => KeyCode::PageUp,
+				"pagedown" => KeyCode::PageDown,
+				"home" => KeyCode::Home,
+				"end" => KeyCode::End,
+				"tab" => KeyCode::Tab,
+				"backtab" => KeyCode::BackTab,
+				"esc" => KeyCode::Esc,
+				"enter" => KeyCode::Enter,
+				"backspace" => KeyCode::Backspace,
+				"delete" => KeyCode::Delete,
+				"insert" => KeyCode::Insert,
+				f => match f.strip_prefix('f').and_then(|x| x.parse().ok()) {
+					Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+					_ => return Err(err()),
+				},
+			},
+		};
+
+		Ok(Key::new(code, modifiers))
+	}
+}
+
+impl Display for Key {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.modifiers.contains(KeyModifiers::CONTROL) { write!(f, "ctrl+")? }
+		if self.modifiers.contains(KeyModifiers::ALT) { write!(f, "alt+")? }
+		if self.modifiers.contains(KeyModifiers::SHIFT) { write!(f, "shift+")? }
+		match self.code {
+			KeyCode::Char(' ') => write!(f, "space"),
+			KeyCode::Char(c) => write!(f, "{}", c),
+			KeyCode::F(n) => write!(f, "f{}", n),
+			KeyCode::PageUp => write!(f, "pageup"),
+			KeyCode::PageDown => write!(f, "pagedown"),
+			KeyCode::BackTab => write!(f, "backtab"),
+			other => write!(f, "{}", format!("{:?}", other).to_lowercase()),
+		}
+	}
+}
+
+/// one key or a list of keys, as written in configuration file
+#[derive(Debug, Clone, Default)]
+pub struct Keys(pub Vec<Key>);
+
+impl<'de> serde::Deserialize<'de> for Keys {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		#[derive(serde::Deserialize)]
+		#[serde(untagged)]
+		enum Raw {
+			One(String),
+			Many(Vec<String>),
+		}
+		let keys = match Raw::deserialize(deserializer)? {
+			Raw::One(key) => vec![key],
+			Raw::Many(keys) => keys,
+		};
+		keys.iter()
+			.map(|x| x.parse().map_err(serde::de::Error::custom))
+			.collect::<Result<Vec<Key>, D::Error>>()
+			.map(Keys)
+	}
+}
+
+/// how much modifiers scale increments and decrements, multiplied together when combined
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Multipliers {
+	pub shift: f64,
+	pub ctrl: f64,
+	pub alt: f64,
+}
+
+impl Default for Multipliers {
+	fn default() -> Self {
+		Multipliers { shift: 10.0, ctrl: 5.0, alt: 0.2 }
+	}
+}
+
+/// which keys trigger which actions, and with what magnitude. the same key may trigger actions of different modes
+#[derive(Debug, Clone)]
+pub struct Keymap {
+	bindings: Vec<(Key, Action, f64)>,
+	pub multipliers: Multipliers,
+}
+
+impl Default for Keymap {
+	fn default() -> Self {
+		use KeyCode::*;
+		let ctrl = |c| Key::new(Char(c), KeyModifiers::CONTROL);
+		let key = |c| Key::plain(Char(c));
+		let bindings = vec![
+			(key('q'), Action::Quit),
+			(ctrl('c'), Action::Quit), // mimic other programs shortcuts to quit, for user friendlyness
+			(ctrl('q'), Action::Quit),
+			(ctrl('w'), Action::Quit),
+			(key('?'), Action::Help),
+			(key(' '), Action::Pause),
+			(key('s'), Action::Scatter),
+			(key('h'), Action::ToggleUi),
+			(key('r'), Action::References),
+			(key('g'), Action::Graticule),
+			(key('x'), Action::Snapshot),
+			(key('c'), Action::Theme),
+			(Key::plain(Tab), Action::NextMode),
+			(Key::plain(Esc), Action::Reset),
+			(Key::plain(Up), Action::YRangeUp),
+			(Key::plain(Down), Action::YRangeDown),
+			(Key::plain(Right), Action::XRangeUp),
+			(Key::plain(Left), Action::XRangeDown),
+			(key('k'), Action::Cursor),
+			(key(']'), Action::CursorForward),
+			(key('['), Action::CursorBack),
+			(key('K'), Action::ClearCursors),
+			(key('m'), Action::Measurements),
+			(key('>'), Action::MeasureAverageUp),
+			(key('<'), Action::MeasureAverageDown),
+			(key('f'), Action::Persistence),
+			(key('F'), Action::InfinitePersistence),
+			(key('D'), Action::DecayUp),
+			(key('d'), Action::DecayDown),
+			(key('v'), Action::Heatmap),
+			(key('V'), Action::Colormap),
+
+			(key('t'), Action::Trigger),
+			(key('e'), Action::Edge),
+			(key('p'), Action::Peaks),
+			(key('u'), Action::Sinc),
+			(key('C'), Action::SelectChannel),
+			(key('I'), Action::Invert),
+			(key('A'), Action::Coupling),
+			(key('H'), Action::HideChannel),
+			(Key::plain(PageUp), Action::ThresholdUp),
+			(Key::plain(PageDown), Action::ThresholdDown),
+			(key('='), Action::HysteresisUp),
+			(key('-'), Action::HysteresisDown),
+			(key('i'), Action::TriggerSource),
+			(key(','), Action::TriggerLeft),
+			(key('.'), Action::TriggerRight),
+			(key('T'), Action::TriggerMode),
+			(key('a'), Action::Arm),
+			(key(')'), Action::HoldoffUp),
+			(key('('), Action::HoldoffDown),
+			(key('y'), Action::TriggerType),
+			(key('B'), Action::BandUp),
+			(key('b'), Action::BandDown),
+			(key('}'), Action::WidthUp),
+			(key('{'), Action::WidthDown),
+			(key('L'), Action::Longer),
+
+			(Key::plain(PageUp), Action::AverageUp),
+			(Key::plain(PageDown), Action::AverageDown),
+			(key('w'), Action::Window),
+			(key('l'), Action::LogY),
+			(key('n'), Action::Grid),
+		];
+		// shifted symbols come without shift modifier, so these can't rely on multipliers for coarse steps
+		let coarse = vec![
+			(key('+'), Action::HysteresisUp),
+			(key('_'), Action::HysteresisDown),
+		];
+		let bindings = bindings.into_iter().map(|(key, action)| (key, action, 1.0))
+			.chain(coarse.into_iter().map(|(key, action)| (key, action, 10.0)))
+			.collect();
+		Keymap { bindings, multipliers: Multipliers::default() }
+	}
+}
+
+impl Keymap {
+	/// default keymap with given actions rebound
+	pub fn with(keys: &BTreeMap<Action, Keys>, multipliers: Multipliers) -> Self {
+		let mut keymap = Keymap { multipliers, ..Default::default() };
+		for (action, keys) in keys {
+			keymap.bind(*action, keys.0.clone());
+		}
+		keymap
+	}
+
+	/// replace keys bound to given action
+	pub fn bind(&mut self, action: Action, keys: Vec<Key>) {
+		self.bindings.retain(|(_, x, _)| *x != action);
+		self.bindings.extend(keys.into_iter().map(|key| (key, action, 1.0)));
+	}
+
+	/// keys bound to given action
+	pub fn keys(&self, action: Action) -> Vec<Key> {
+		self.bindings.iter()
+			.filter(|(_, x, _)| *x == action)
+			.map(|(key, _, _)| *key)
+			.collect()
+	}
+
+	/// actions bound to given key event, with the magnitude they should be applied with.
+	/// keys bound with modifiers match exactly, otherwise modifiers act as multipliers
+	pub fn actions(&self, event: KeyEvent) -> Vec<(Action, f64)> {
+		let event = Key::new(event.code, event.modifiers);
+		let exact : Vec<(Action, f64)> = self.bindings.iter()
+			.filter(|(key, _, _)| *key == event)
+			.map(|(_, action, magnitude)| (*action, *magnitude))
+			.collect();
+		if !exact.is_empty() || event.modifiers.is_empty() { return exact }
+
+		let scale = self.magnitude(event.modifiers);
+		self.bindings.iter()
+			.filter(|(key, _, _)| key.code == event.code && key.modifiers.is_empty())
+			.map(|(_, action, magnitude)| (*action, magnitude * scale))
+			.collect()
+	}
+
+	/// keys bound to any of given actions, like "pageup / pagedown"
+	pub fn describe(&self, actions: &[Action]) -> String {
+		actions.iter()
+			.map(|action| self.keys(*action).iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", "))
+			.filter(|x| !x.is_empty())
+			.collect::<Vec<String>>()
+			.join(" / ")
+	}
+
+	fn magnitude(&self, modifiers: KeyModifiers) -> f64 {
+		let mut magnitude = 1.0;
+		if modifiers.contains(KeyModifiers::SHIFT) { magnitude *= self.multipliers.shift }
+		if modifiers.contains(KeyModifiers::CONTROL) { magnitude *= self.multipliers.ctrl }
+		if modifiers.contains(KeyModifiers::ALT) { magnitude *= self.multipliers.alt }
+		magnitude
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+		KeyEvent::new(code, modifiers)
+	}
+
+	#[test]
+	fn parse_plain_and_named_keys() {
+		assert_eq!("q".parse::<Key>().unwrap(), Key::plain(KeyCode::Char('q')));
+		assert_eq!("space".parse::<Key>().unwrap(), Key::plain(KeyCode::Char(' ')));
+		assert_eq!("PageUp".parse::<Key>().unwrap(), Key::plain(KeyCode::PageUp));
+		assert_eq!("f12".parse::<Key>().unwrap(), Key::plain(KeyCode::F(12)));
+	}
+
+	#[test]
+	fn parse_modifiers() {
+		assert_eq!("ctrl+c".parse::<Key>().unwrap(), Key::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+		assert_eq!("Control+Alt+left".parse::<Key>().unwrap(), Key::new(KeyCode::Left, KeyModifiers::CONTROL | KeyModifiers::ALT));
+		assert_eq!("shift+pageup".parse::<Key>().unwrap(), Key::new(KeyCode::PageUp, KeyModifiers::SHIFT));
+	}
+
+	#[test]
+	fn parse_plus_as_key() {
+		assert_eq!("+".parse::<Key>().unwrap(), Key::plain(KeyCode::Char('+')));
+		assert_eq!("ctrl++".parse::<Key>().unwrap(), Key::new(KeyCode::Char('+'), KeyModifiers::CONTROL));
+	}
+
+	#[test]
+	fn parse_invalid_keys() {
+		assert!("".parse::<Key>().is_err());
+		assert!("f13".parse::<Key>().is_err());
+		assert!("f0".parse::<Key>().is_err());
+		assert!("hyper+a".parse::<Key>().is_err());
+		assert!("ctrl+".parse::<Key>().is_err());
+		assert!("nothing".parse::<Key>().is_err());
+	}
+
+	#[test]
+	fn shifted_letters_are_uppercase() {
+		let upper = Key::plain(KeyCode::Char('B'));
+		assert_eq!("B".parse::<Key>().unwrap(), upper);
+		assert_eq!("shift+b".parse::<Key>().unwrap(), upper);
+		assert_eq!(Key::new(KeyCode::Char('B'), KeyModifiers::SHIFT), upper);
+		assert_eq!("shift+ctrl+b".parse::<Key>().unwrap(), Key::new(KeyCode::Char('B'), KeyModifiers::CONTROL));
+	}
+
+	#[test]
+	fn display_roundtrips() {
+		for txt in ["q", "ctrl+c", "alt+shift+pageup", "space", "f5", "backtab", "ctrl++", "K"] {
+			let key : Key = txt.parse().unwrap();
+			assert_eq!(key.to_string(), txt);
+			assert_eq!(key.to_string().parse::<Key>().unwrap(), key);
+		}
+	}
+
+	#[test]
+	fn uppercase_letters_match_exactly() {
+		let keymap = Keymap::default();
+		assert_eq!(keymap.actions(event(KeyCode::Char('B'), KeyModifiers::SHIFT)), vec![(Action::BandUp, 1.0)]);
+		assert_eq!(keymap.actions(event(KeyCode::Char('b'), KeyModifiers::NONE)), vec![(Action::BandDown, 1.0)]);
+	}
+
+	#[test]
+	fn modifiers_scale_plain_bindings() {
+		let keymap = Keymap::default();
+		assert_eq!(keymap.actions(event(KeyCode::Up, KeyModifiers::SHIFT)), vec![(Action::YRangeUp, 10.0)]);
+		assert_eq!(keymap.actions(event(KeyCode::Up, KeyModifiers::SHIFT | KeyModifiers::CONTROL)), vec![(Action::YRangeUp, 50.0)]);
+		assert_eq!(keymap.actions(event(KeyCode::Char('q'), KeyModifiers::CONTROL)), vec![(Action::Quit, 1.0)]);
+		assert!(keymap.actions(event(KeyCode::Char('z'), KeyModifiers::NONE)).is_empty());
+	}
+
+	#[test]
+	fn coarse_bindings_keep_magnitude() {
+		let keymap = Keymap::default();
+		assert_eq!(keymap.actions(event(KeyCode::Char('='), KeyModifiers::NONE)), vec![(Action::HysteresisUp, 1.0)]);
+		assert_eq!(keymap.actions(event(KeyCode::Char('+'), KeyModifiers::NONE)), vec![(Action::HysteresisUp, 10.0)]);
+		assert_eq!(keymap.actions(event(KeyCode::Char('_'), KeyModifiers::NONE)), vec![(Action::HysteresisDown, 10.0)]);
+	}
+
+	#[test]
+	fn rebinding_replaces_keys() {
+		let mut keymap = Keymap::default();
+		keymap.bind(Action::Quit, vec!["ctrl+x".parse().unwrap()]);
+		assert!(keymap.actions(event(KeyCode::Char('q'), KeyModifiers::NONE)).is_empty());
+		assert_eq!(keymap.keys(Action::Quit), vec![Key::new(KeyCode::Char('x'), KeyModifiers::CONTROL)]);
+	}
+}
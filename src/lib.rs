@@ -0,0 +1,17 @@
+//! oscilloscope, vectorscope and spectroscope for the terminal.
+//!
+//! audio comes from a [DataSource] as a [Matrix] of normalized samples, one row per channel.
+//! any [DisplayMode] turns it into datasets, which the [Scope] widget draws inside any ratatui
+//! interface, styled by a [GraphConfig]. the [App] puts it all together, as seen in scope-tui
+
+pub mod app;
+pub mod music;
+pub mod input;
+pub mod display;
+pub mod keymap;
+mod snapshot;
+
+pub use app::{App, AppBuilder, CurrentDisplayMode};
+pub use display::{DataSet, Dimension, DisplayMode, GraphConfig, Scope, Theme};
+pub use input::{DataSource, Matrix, StreamOptions};
+pub use snapshot::{Size, SizeError, SnapshotError};
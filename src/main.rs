@@ -1,56 +1,133 @@
-mod app;
 mod cfg;
-mod music;
-mod input;
-mod display;
+mod render;
+mod cast;
+mod analyze;
 
-use app::App;
-use cfg::{ScopeArgs, ScopeSource};
-use clap::Parser;
+use std::{io::{self, Write}, time::Instant};
+
+use scope_tui::{
+	input,
+	app::{EventSource, Exit, TerminalEvents},
+	App, DataSource, Size, StreamOptions,
+};
 use ratatui::{backend::CrosstermBackend, Terminal};
-use crossterm::{execute, terminal::{
+use crossterm::{execute, event::{DisableMouseCapture, EnableMouseCapture, Event}, terminal::{
 	disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen
 }};
 
+use crate::{
+	cast::{CastRecorder, Clock},
+	cfg::{Config, ConfigWatch, ScopeArgs, ScopeCommand, ScopeSource},
+};
+
+/// terminal events, and changes to configuration file if watching it
+struct Events(Option<ConfigWatch>);
+
+impl EventSource for Events {
+	fn poll(&mut self) -> Result<Option<Event>, io::Error> {
+		TerminalEvents.poll()
+	}
+
+	fn reload(&mut self, app: &mut App) -> bool {
+		self.0.as_mut().is_some_and(|watch| watch.apply(app))
+	}
+}
+
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-	let mut args = ScopeArgs::parse();
+	let config = Config::from_env()
+		.and_then(|config| Ok((ScopeArgs::parse_with(&config)?, config)));
+	let (mut args, config) = match config {
+		Ok(x) => x,
+		Err(e) => {
+			eprintln!("[!] {}", e);
+			std::process::exit(2);
+		},
+	};
 	args.opts.tune();
 
-	let source = match args.source {
-		#[cfg(feature = "pulseaudio")]
-		ScopeSource::Pulse { device, server_buffer } => {
-			input::pulse::PulseAudioSimpleDataSource::new(device.as_deref(), &args.opts, server_buffer)?
+	let (source, render_opts) = match args.command.clone() {
+		None => (None, None),
+		Some(ScopeCommand::Source(source)) => (Some(source), None),
+		Some(ScopeCommand::Render { opts, source }) => (source, Some(opts)),
+		Some(ScopeCommand::Analyze { opts, source }) => {
+			let source = open_source(source.or(config.source), &args.opts.stream())?;
+			let report = analyze::analyze(source, args.opts.sample_rate, opts.duration);
+			if opts.json {
+				println!("{}", serde_json::to_string_pretty(&report)?);
+			} else {
+				print!("{}", report);
+			}
+			return Ok(());
 		},
+	};
 
-		#[cfg(feature = "file")]
-		ScopeSource::File { path, limit_rate } => {
-			input::file::FileSource::new(&path, &args.opts, limit_rate)?
-		},
+	let source = source.or(config.source.clone());
+	let mut current = (source.clone(), args.opts.stream()); // to fall back on if restarting fails
+	let mut source = open_source(source, &current.1)?;
 
-		#[cfg(feature = "cpal")]
-		ScopeSource::Audio { device, timeout } => {
-			input::cpal::DefaultAudioDeviceWithCPAL::new(device.as_deref(), &args.opts, timeout)?
-		}
+	let mut app = args.app(&config).build();
+	let mut events = match (Config::path_from_env(), &render_opts, &args.snapshot) {
+		(Some(path), None, None) => Events(Some(ConfigWatch::new(path, args.clone(), config))),
+		_ => Events(None),
 	};
 
-	let mut app = App::new(&args.ui, &args.opts);
+	if let Some(path) = &args.snapshot {
+		let data = source.recv().ok_or("data source returned null")?;
+		app.update(data);
+		app.export(path)?;
+		return Ok(());
+	}
+
+	if let Some(opts) = render_opts {
+		render::render(&mut app, source, &opts, args.opts.sample_rate)?;
+		return Ok(());
+	}
+
+	let stdout = std::io::stdout();
+	let mut writer : Box<dyn Write> = match &args.record {
+		None => Box::new(stdout),
+		Some(path) => {
+			let (width, height) = crossterm::terminal::size()?;
+			let size = Size { width: width as u32, height: height as u32 };
+			Box::new(CastRecorder::new(stdout, path, size, Clock::Wall(Instant::now()))?)
+		},
+	};
 
 	// setup terminal
 	enable_raw_mode()?;
-	let mut stdout = std::io::stdout();
-	execute!(stdout, EnterAlternateScreen)?;
-	let backend = CrosstermBackend::new(stdout);
+	execute!(writer, EnterAlternateScreen, EnableMouseCapture)?;
+	let backend = CrosstermBackend::new(writer);
 	let mut terminal = Terminal::new(backend)?;
 	terminal.hide_cursor()?;
 
-	let res = app.run(source, &mut terminal);
+	let mut res = app.run(source, &mut terminal, &mut events);
+	while let Ok(Exit::Restart) = res {
+		let watch = events.0.as_ref().expect("restart requested without configuration");
+		let next = (watch.args.source().or(watch.config.source.as_ref()).cloned(), watch.args.opts.stream());
+		let source = match open_source(next.0.clone(), &next.1) {
+			Ok(source) => {
+				app.set_stream(&next.1);
+				current = next;
+				source
+			},
+			Err(e) => {
+				app.set_status(format!("could not restart capture: {}", e));
+				match open_source(current.0.clone(), &current.1) {
+					Ok(source) => source,
+					Err(e) => { res = Err(std::io::Error::other(e.to_string())); break },
+				}
+			},
+		};
+		res = app.run(source, &mut terminal, &mut events);
+	}
 
 	// restore terminal
 	disable_raw_mode()?;
 	execute!(
 		terminal.backend_mut(),
 		LeaveAlternateScreen,
+		DisableMouseCapture,
 	)?;
 	terminal.show_cursor()?;
 
@@ -60,3 +137,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 	Ok(())
 }
+
+fn open_source(source: Option<ScopeSource>, opts: &StreamOptions) -> Result<Box<dyn DataSource<f64>>, Box<dyn std::error::Error>> {
+	let source = source.ok_or("no source given, pass one as subcommand or set a default in configuration file")?;
+	Ok(match source {
+		#[cfg(feature = "pulseaudio")]
+		ScopeSource::Pulse { device, server_buffer } => {
+			input::pulse::PulseAudioSimpleDataSource::new(device.as_deref(), opts, server_buffer)?
+		},
+
+		#[cfg(feature = "file")]
+		ScopeSource::File { path, limit_rate } => {
+			input::file::FileSource::new(&path, opts, limit_rate)?
+		},
+
+		#[cfg(feature = "cpal")]
+		ScopeSource::Audio { device, timeout } => {
+			input::cpal::DefaultAudioDeviceWithCPAL::new(device.as_deref(), opts, timeout)?
+		}
+	})
+}
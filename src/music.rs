@@ -1,10 +1,15 @@
-use std::{str::FromStr, num::ParseIntError};
+use std::{fmt::Display, str::FromStr, num::ParseIntError};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Tone {
 	C, Db, D, Eb, E, F, Gb, G, Ab, A, Bb, B
 }
 
+/// all tones in an octave, in ascending order
+pub const TONES : [Tone; 12] = [
+	Tone::C, Tone::Db, Tone::D, Tone::Eb, Tone::E, Tone::F, Tone::Gb, Tone::G, Tone::Ab, Tone::A, Tone::Bb, Tone::B
+];
+
 #[derive(Debug, thiserror::Error, derive_more::Display)]
 pub struct ToneError();
 
@@ -64,7 +69,47 @@ impl FromStr for Tone {
 	}
 }
 
+impl Display for Tone {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let name = match self {
+			Tone::C  => "C",
+			Tone::Db => "C#",
+			Tone::D  => "D",
+			Tone::Eb => "D#",
+			Tone::E  => "E",
+			Tone::F  => "F",
+			Tone::Gb => "F#",
+			Tone::G  => "G",
+			Tone::Ab => "G#",
+			Tone::A  => "A",
+			Tone::Bb => "A#",
+			Tone::B  => "B",
+		};
+		write!(f, "{}", name)
+	}
+}
+
+impl Display for Note {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}{}", self.tone, self.octave)
+	}
+}
+
 impl Note {
+	/// closest note to given frequency, and how far from it the frequency is, in cents
+	pub fn nearest(freq: f64) -> Option<(Note, f64)> {
+		let semitones = (12.0 * (freq / Tone::C.freq(0) as f64).log2()).round();
+		if !semitones.is_finite() || semitones < 0.0 { return None }
+		let semitones = semitones as u32;
+		let note = Note { tone: TONES[(semitones % 12) as usize].clone(), octave: semitones / 12 };
+		let cents = 1200.0 * (freq / note.freq()).log2();
+		Some((note, cents))
+	}
+
+	pub fn freq(&self) -> f64 {
+		self.tone.freq(self.octave) as f64
+	}
+
 	pub fn tune_buffer_size(&self, sample_rate: u32) -> u32 {
 		let t = 1.0 / self.tone.freq(self.octave); // periodo ?
 		let buf = (sample_rate as f32) * t;
@@ -100,3 +145,33 @@ impl Tone {
 	}
 }
 
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn nearest_note_of_exact_pitch() {
+		let (note, cents) = Note::nearest(440.0).unwrap();
+		assert_eq!(note.to_string(), "A4");
+		assert!(cents.abs() < 1.0, "{cents} cents");
+	}
+
+	#[test]
+	fn nearest_note_reports_detune() {
+		let (note, cents) = Note::nearest(440.0 * 2f64.powf(30.0 / 1200.0)).unwrap();
+		assert_eq!(note.to_string(), "A4");
+		assert!((cents - 30.0).abs() < 1.0, "{cents} cents");
+		let (note, cents) = Note::nearest(440.0 * 2f64.powf(-70.0 / 1200.0)).unwrap();
+		assert_eq!(note.to_string(), "G#4");
+		assert!((cents - 30.0).abs() < 1.0, "{cents} cents");
+	}
+
+	#[test]
+	fn no_note_below_range_or_for_silence() {
+		assert!(Note::nearest(0.0).is_none());
+		assert!(Note::nearest(-440.0).is_none());
+		assert!(Note::nearest(f64::NAN).is_none());
+		assert!(Note::nearest(1.0).is_none());
+	}
+}
@@ -0,0 +1,96 @@
+use std::{fs::File, io::{self, BufWriter, Write}, path::PathBuf};
+
+use ratatui::{backend::{CrosstermBackend, TestBackend}, layout::Rect, Terminal, TerminalOptions, Viewport};
+
+use scope_tui::{App, DataSource, Size, SnapshotError};
+
+use crate::{cast::{CastRecorder, Clock, ClockedSource, StreamClock}, cfg::RenderOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RenderFormat {
+	/// plain text frames, separated by form feeds
+	Text,
+	/// one svg image per frame
+	Svg,
+	/// one png image per frame
+	#[cfg(feature = "png")]
+	Png,
+	/// asciinema v2 recording
+	Cast,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RenderError {
+	#[error("{0}")]
+	Io(#[from] io::Error),
+
+	#[error("{0}")]
+	Snapshot(#[from] SnapshotError),
+
+	#[error("frame size {0} must be between 1x1 and {max}x{max}", max = u16::MAX)]
+	InvalidSize(Size),
+
+	#[error("an output file is required for {0:?} format")]
+	MissingOutput(RenderFormat),
+}
+
+/// drive app over every frame from source, without a terminal or user events
+pub fn render(app: &mut App, mut source: Box<dyn DataSource<f64>>, opts: &RenderOptions, sample_rate: u32) -> Result<(), RenderError> {
+	let (Ok(width @ 1..), Ok(height @ 1..)) = (u16::try_from(opts.size.width), u16::try_from(opts.size.height)) else {
+		return Err(RenderError::InvalidSize(opts.size));
+	};
+	let area = Rect::new(0, 0, width, height);
+	app.set_offline();
+	match opts.format {
+		RenderFormat::Text => {
+			let mut out : Box<dyn Write> = match &opts.output {
+				Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+				None => Box::new(io::stdout().lock()),
+			};
+			let mut terminal = Terminal::new(TestBackend::new(area.width, area.height))?;
+			while let Some(data) = source.recv() {
+				app.update(data);
+				app.draw(&mut terminal)?;
+				let buffer = terminal.backend().buffer();
+				for y in 0..area.height {
+					let line : String = (0..area.width).map(|x| buffer.get(x, y).symbol()).collect();
+					writeln!(out, "{}", line.trim_end())?;
+				}
+				writeln!(out, "\x0c")?;
+			}
+			out.flush()?;
+		},
+
+		#[cfg(feature = "png")]
+		RenderFormat::Png => render_images(app, source, opts, "png")?,
+
+		RenderFormat::Svg => render_images(app, source, opts, "svg")?,
+
+		RenderFormat::Cast => {
+			let path = opts.output.as_deref().ok_or(RenderError::MissingOutput(opts.format))?;
+			let clock = StreamClock::default();
+			let mut source = ClockedSource::new(source, clock.clone(), sample_rate);
+			let recorder = CastRecorder::new(io::sink(), path, opts.size, Clock::Stream(clock))?;
+			let viewport = Viewport::Fixed(area);
+			let mut terminal = Terminal::with_options(CrosstermBackend::new(recorder), TerminalOptions { viewport })?;
+			terminal.hide_cursor()?;
+			while let Some(data) = source.recv() {
+				app.update(data);
+				app.draw(&mut terminal)?;
+			}
+		},
+	}
+	Ok(())
+}
+
+/// save one image per frame in output directory, format chosen by extension
+fn render_images(app: &mut App, mut source: Box<dyn DataSource<f64>>, opts: &RenderOptions, ext: &str) -> Result<(), RenderError> {
+	let dir = opts.output.clone().unwrap_or(PathBuf::from("."));
+	let mut count = 0;
+	while let Some(data) = source.recv() {
+		app.update(data);
+		app.export(&dir.join(format!("frame-{:06}.{}", count, ext)))?;
+		count += 1;
+	}
+	Ok(())
+}
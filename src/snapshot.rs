@@ -0,0 +1,239 @@
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use ratatui::{style::Color, widgets::GraphType};
+
+use crate::display::{theme::rgb, DataSet, Dimension, DisplayMode, GraphConfig};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+	#[error("{0}")]
+	Io(#[from] std::io::Error),
+
+	#[cfg(feature = "png")]
+	#[error("unsupported snapshot format '{0}', use .svg or .png")]
+	UnsupportedFormat(String),
+
+	#[cfg(not(feature = "png"))]
+	#[error("unsupported snapshot format '{0}', use .svg (built without png support)")]
+	UnsupportedFormat(String),
+
+	#[cfg(feature = "png")]
+	#[error("could not rasterize snapshot: {0}")]
+	Rasterize(String),
+}
+
+/// image resolution in pixels, or frame size in terminal cells, written like "1920x1080"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+	pub width: u32,
+	pub height: u32,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid size '{0}', expected WIDTHxHEIGHT")]
+pub struct SizeError(String);
+
+impl FromStr for Size {
+	type Err = SizeError;
+
+	fn from_str(txt: &str) -> Result<Self, Self::Err> {
+		let (width, height) = txt.trim().split_once('x').ok_or(SizeError(txt.into()))?;
+		Ok(Size {
+			width: width.parse().map_err(|_| SizeError(txt.into()))?,
+			height: height.parse().map_err(|_| SizeError(txt.into()))?,
+		})
+	}
+}
+
+impl Display for Size {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}x{}", self.width, self.height)
+	}
+}
+
+/// render datasets as a standalone svg document, in full precision rather than terminal cells.
+/// bounds and axis names come from given display mode, colors from graph config
+pub fn svg(cfg: &GraphConfig, display: &dyn DisplayMode, datasets: &[DataSet], size: Size) -> String {
+	let (width, height) = (size.width as f64, size.height as f64);
+	let font = (height / 40.0).max(10.0);
+	let stroke = (width / 1000.0).max(1.0);
+	let top = if cfg.show_ui { font * 2.0 } else { 0.0 };
+	let (left, right, bottom) = if cfg.show_ui { (font, width - font, height - font * 1.5) } else { (0.0, width, height) };
+
+	let [x_min, x_max] = display.view(cfg, Dimension::X);
+	let [y_min, y_max] = display.view(cfg, Dimension::Y);
+	let point = |(x, y): (f64, f64)| (
+		left + (x - x_min) / (x_max - x_min) * (right - left),
+		bottom - (y - y_min) / (y_max - y_min) * (bottom - top),
+	);
+
+	let mut out = format!(
+		"<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\" font-family=\"monospace\" font-size=\"{font:.1}\">\n"
+	);
+	out.push_str(&format!("<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>\n", hex(background(cfg))));
+	out.push_str(&format!(
+		"<clipPath id=\"graph\"><rect x=\"{left:.2}\" y=\"{top:.2}\" width=\"{:.2}\" height=\"{:.2}\"/></clipPath>\n",
+		right - left, bottom - top,
+	));
+
+	if cfg.show_ui {
+		out.push_str(&format!(
+			"<text x=\"{:.2}\" y=\"{:.2}\" fill=\"{}\" font-weight=\"bold\">{}::scope-tui</text>\n",
+			font, font * 1.2, hex(cfg.palette(0)), display.mode_str(),
+		));
+		out.push_str(&format!(
+			"<text x=\"{:.2}\" y=\"{:.2}\" fill=\"{}\">{}  -{:.2}x+  {}/{} spf</text>\n",
+			width * 0.35, font * 1.2, hex(cfg.labels_color),
+			escape(&display.header(cfg)), cfg.scale, cfg.samples, cfg.width,
+		));
+		out.push_str(&format!(
+			"<text x=\"{left:.2}\" y=\"{:.2}\" fill=\"{}\">{}</text>\n",
+			top + font, hex(cfg.labels_color), escape(display.axis_name(Dimension::Y)),
+		));
+		out.push_str(&format!(
+			"<text x=\"{right:.2}\" y=\"{:.2}\" fill=\"{}\" text-anchor=\"end\">{}</text>\n",
+			bottom + font * 1.2, hex(cfg.labels_color), escape(display.axis_name(Dimension::X)),
+		));
+		// tick labels inside plot edges, spread like in the terminal
+		let x_labels = display.labels(cfg, Dimension::X);
+		for (i, label) in x_labels.iter().enumerate() {
+			let t = i as f64 / (x_labels.len() - 1).max(1) as f64;
+			let anchor = if i == 0 { "start" } else if i + 1 == x_labels.len() { "end" } else { "middle" };
+			out.push_str(&format!(
+				"<text x=\"{:.2}\" y=\"{:.2}\" fill=\"{}\" text-anchor=\"{anchor}\">{}</text>\n",
+				left + t * (right - left), bottom - font * 0.3, hex(cfg.labels_color), escape(label),
+			));
+		}
+		let y_labels = display.labels(cfg, Dimension::Y);
+		for (i, label) in y_labels.iter().enumerate().skip(1) { // first one would overlap X labels, on the right
+			let t = i as f64 / (y_labels.len() - 1).max(1) as f64;
+			out.push_str(&format!(
+				"<text x=\"{:.2}\" y=\"{:.2}\" fill=\"{}\" text-anchor=\"end\">{}</text>\n",
+				right - font * 0.3, bottom - t * (bottom - top) + font, hex(cfg.labels_color), escape(label),
+			));
+		}
+	}
+
+	out.push_str("<g clip-path=\"url(#graph)\">\n");
+	for ds in datasets {
+		let color = hex(ds.color);
+		match ds.graph_type {
+			GraphType::Line => {
+				let points = ds.data.iter()
+					.map(|p| point(*p))
+					.map(|(x, y)| format!("{x:.2},{y:.2}"))
+					.collect::<Vec<String>>()
+					.join(" ");
+				out.push_str(&format!(
+					"<polyline points=\"{points}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"{stroke:.2}\" stroke-linejoin=\"round\"/>\n"
+				));
+			},
+			_ => { // draw every point as a tiny square, circles get huge with thousands of points
+				let path = ds.data.iter()
+					.map(|p| point(*p))
+					.map(|(x, y)| format!("M{:.2} {:.2}h{stroke:.2}v{stroke:.2}h-{stroke:.2}z", x - stroke / 2.0, y - stroke / 2.0))
+					.collect::<String>();
+				out.push_str(&format!("<path d=\"{path}\" fill=\"{color}\"/>\n"));
+			},
+		}
+	}
+	if cfg.references && cfg.show_ui { // like in the terminal, labels start right of their point
+		for (x, y, label) in display.annotations(cfg) {
+			let (x, y) = point((x, y));
+			out.push_str(&format!(
+				"<text x=\"{:.2}\" y=\"{:.2}\" fill=\"{}\">{}</text>\n",
+				x + font * 0.3, (y + font).min(bottom - font * 0.3), hex(cfg.labels_color), escape(&label),
+			));
+		}
+	}
+	out.push_str("</g>\n");
+
+	let names : Vec<(&str, Color)> = datasets.iter()
+		.filter_map(|ds| Some((ds.name.as_deref()?, ds.color)))
+		.collect();
+	if !names.is_empty() { // same as ratatui's chart legend, top right
+		let longest = names.iter().map(|(name, _)| name.len()).max().unwrap_or(0) as f64;
+		let (w, h) = ((longest + 2.0) * font * 0.6, (names.len() as f64 + 1.0) * font * 1.2);
+		let x = right - w - font;
+		out.push_str(&format!(
+			"<rect x=\"{x:.2}\" y=\"{top:.2}\" width=\"{w:.2}\" height=\"{h:.2}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{stroke:.2}\"/>\n",
+			hex(cfg.axis_color),
+		));
+		for (i, (name, color)) in names.iter().enumerate() {
+			out.push_str(&format!(
+				"<text x=\"{:.2}\" y=\"{:.2}\" fill=\"{}\">{}</text>\n",
+				x + font * 0.6, top + (i as f64 + 1.2) * font * 1.2, hex(*color), escape(name),
+			));
+		}
+	}
+
+	out.push_str("</svg>\n");
+	out
+}
+
+#[cfg(feature = "png")]
+pub fn png(svg: &str) -> Result<Vec<u8>, SnapshotError> {
+	use resvg::{usvg, tiny_skia};
+	let mut opts = usvg::Options::default();
+	opts.fontdb_mut().load_system_fonts();
+	let tree = usvg::Tree::from_str(svg, &opts)
+		.map_err(|e| SnapshotError::Rasterize(e.to_string()))?;
+	let size = tree.size().to_int_size();
+	let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+		.ok_or(SnapshotError::Rasterize("invalid image size".into()))?;
+	resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+	pixmap.encode_png()
+		.map_err(|e| SnapshotError::Rasterize(e.to_string()))
+}
+
+/// write svg document to given path, rasterizing it if path asks for a png
+pub fn save(path: &Path, svg: &str) -> Result<(), SnapshotError> {
+	match path.extension().and_then(|x| x.to_str()) {
+		Some("svg") => std::fs::write(path, svg)?,
+		#[cfg(feature = "png")]
+		Some("png") => std::fs::write(path, png(svg)?)?,
+		other => return Err(SnapshotError::UnsupportedFormat(other.unwrap_or("").into())),
+	}
+	Ok(())
+}
+
+/// terminal background is unknown, guess it from labels: dark labels are meant for light backgrounds
+fn background(cfg: &GraphConfig) -> Color {
+	let (r, g, b) = rgb(cfg.labels_color);
+	let luma = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+	if luma < 128.0 { Color::White } else { Color::Black }
+}
+
+fn escape(txt: &str) -> String {
+	txt.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// convert terminal colors to hex, using xterm default palette for named and indexed ones
+pub fn hex(color: Color) -> String {
+	let (r, g, b) = rgb(color);
+	format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn size_parses_width_by_height() {
+		assert_eq!("1920x1080".parse::<Size>().unwrap(), Size { width: 1920, height: 1080 });
+		assert_eq!(" 80x24 ".parse::<Size>().unwrap(), Size { width: 80, height: 24 });
+	}
+
+	#[test]
+	fn size_rejects_malformed_text() {
+		for txt in ["", "1920", "1920x", "x1080", "1920*1080", "-1x10", "axb"] {
+			assert!(txt.parse::<Size>().is_err(), "{txt:?} parsed");
+		}
+	}
+
+	#[test]
+	fn size_displays_as_parsed() {
+		let size = Size { width: 640, height: 480 };
+		assert_eq!(size.to_string().parse::<Size>().unwrap(), size);
+	}
+}